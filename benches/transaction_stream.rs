@@ -0,0 +1,211 @@
+//! Compares the channel based [TransactionStream] against the previous design
+//! that stored the transactions in a `Vec` and made the dealers poll it.
+//!
+//! Run using `cargo bench --bench transaction_stream`.
+use {
+    athanasia::models::{Transaction, TransactionStream, TransactionType},
+    criterion::{criterion_group, criterion_main, BenchmarkId, Criterion},
+    std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    },
+    tokio::{
+        runtime::Runtime,
+        sync::{oneshot, RwLock, Semaphore},
+    },
+};
+
+/// Replica of the previous design: a bounded `Vec` that is dequeued using
+/// `remove(0)` and a dealer that sleeps for a millisecond whenever the queue
+/// is empty.
+struct PollingStream {
+    alive: AtomicBool,
+    free_slots: Semaphore,
+    transaction_queue: RwLock<Vec<Transaction>>,
+}
+
+impl PollingStream {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            alive: AtomicBool::new(true),
+            free_slots: Semaphore::new(capacity),
+            transaction_queue: RwLock::new(Vec::with_capacity(capacity)),
+        }
+    }
+
+    async fn push(&self, transaction: Transaction) {
+        if let Ok(permit) = self.free_slots.acquire().await {
+            permit.forget();
+        }
+        self.transaction_queue.write().await.push(transaction);
+    }
+
+    async fn next(&self) -> Option<Transaction> {
+        let mut q = self.transaction_queue.write().await;
+        if q.is_empty() {
+            return None;
+        }
+        let transaction = q.remove(0);
+        self.free_slots.add_permits(1);
+        Some(transaction)
+    }
+
+    fn close(&self) {
+        self.alive.store(false, Ordering::SeqCst);
+    }
+
+    /// The work loop of the previous dealer implementation.
+    async fn work<F: FnMut(Transaction)>(&self, mut f: F) {
+        loop {
+            let alive = self.alive.load(Ordering::SeqCst);
+            match self.next().await {
+                Some(t) => f(t),
+                None if !alive => break,
+                None => {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+            }
+        }
+    }
+}
+
+fn transaction(tx: u32) -> Transaction {
+    Transaction::new(TransactionType::Deposit, 1, tx, None)
+}
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+/// Pushes `count` transactions through a stream with the same capacity.
+fn handoff(c: &mut Criterion) {
+    let rt = runtime();
+    let mut group = c.benchmark_group("handoff");
+    group.sample_size(10);
+    for count in [1_000u32, 10_000] {
+        group.bench_with_input(
+            BenchmarkId::new("polling", count),
+            &count,
+            |b, &count| {
+                b.iter(|| {
+                    rt.block_on(async {
+                        let s = Arc::new(PollingStream::with_capacity(
+                            count as usize,
+                        ));
+                        let consumer = s.clone();
+                        let h = tokio::spawn(async move {
+                            consumer.work(|_| ()).await;
+                        });
+                        for tx in 0..count {
+                            s.push(transaction(tx)).await;
+                        }
+                        s.close();
+                        h.await.unwrap();
+                    })
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("channel", count),
+            &count,
+            |b, &count| {
+                b.iter(|| {
+                    rt.block_on(async {
+                        let s = Arc::new(TransactionStream::with_capacity(
+                            count as usize,
+                        ));
+                        let consumer = s.clone();
+                        let h = tokio::spawn(async move {
+                            while consumer.next().await.is_some() {}
+                        });
+                        for tx in 0..count {
+                            s.push(transaction(tx)).await;
+                        }
+                        s.close();
+                        h.await.unwrap();
+                    })
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Measures the time an idle dealer needs to pick up a new transaction.
+fn idle_wakeup(c: &mut Criterion) {
+    let rt = runtime();
+    let mut group = c.benchmark_group("idle_wakeup");
+    group
+        .sample_size(10)
+        .warm_up_time(Duration::from_millis(200))
+        .measurement_time(Duration::from_millis(500));
+    group.bench_function("polling", |b| {
+        b.iter_custom(|iters| {
+            rt.block_on(async {
+                let mut elapsed = Duration::ZERO;
+                for _ in 0..iters {
+                    let s = Arc::new(PollingStream::with_capacity(1));
+                    let consumer = s.clone();
+                    let (done, received) = oneshot::channel();
+                    let h = tokio::spawn(async move {
+                        let mut done = Some(done);
+                        consumer
+                            .work(|_| {
+                                if let Some(d) = done.take() {
+                                    let _ = d.send(Instant::now());
+                                }
+                            })
+                            .await;
+                    });
+                    // give the dealer time to run idle
+                    std::thread::sleep(Duration::from_micros(200));
+                    let start = Instant::now();
+                    s.push(transaction(0)).await;
+                    elapsed += received.await.unwrap() - start;
+                    s.close();
+                    h.await.unwrap();
+                }
+                elapsed
+            })
+        })
+    });
+    group.bench_function("channel", |b| {
+        b.iter_custom(|iters| {
+            rt.block_on(async {
+                let mut elapsed = Duration::ZERO;
+                for _ in 0..iters {
+                    let s = Arc::new(TransactionStream::with_capacity(1));
+                    let consumer = s.clone();
+                    let (done, received) = oneshot::channel();
+                    let h = tokio::spawn(async move {
+                        let mut done = Some(done);
+                        while consumer.next().await.is_some() {
+                            if let Some(d) = done.take() {
+                                let _ = d.send(Instant::now());
+                            }
+                        }
+                    });
+                    // give the dealer time to run idle
+                    std::thread::sleep(Duration::from_micros(200));
+                    let start = Instant::now();
+                    s.push(transaction(0)).await;
+                    elapsed += received.await.unwrap() - start;
+                    s.close();
+                    h.await.unwrap();
+                }
+                elapsed
+            })
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, handoff, idle_wakeup);
+criterion_main!(benches);
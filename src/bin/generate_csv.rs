@@ -1,5 +1,5 @@
 use {
-    athanasia::models::{Transaction, TransactionType},
+    athanasia::models::{CreditAmount, Transaction, TransactionType},
     clap::{crate_authors, crate_version, Clap},
     rand::Rng,
 };
@@ -29,7 +29,7 @@ fn main() {
     let account_ids: Vec<u16> = (0..config.account_count).collect();
 
     for _i in 0..config.transaction_count {
-        let amount = rng.gen::<f32>();
+        let amount = CreditAmount::from_raw(rng.gen_range(0..10_000));
         let transaction_type = rng.gen_range(0..5);
         let (transaction_type, amount) = match transaction_type {
             0 => (TransactionType::Deposit, Some(amount)),
@@ -1,59 +1,632 @@
 use {
-    crate::models::{Dealer, Storage, TransactionStream},
-    log::error,
-    std::sync::Arc,
-    tokio::sync::Mutex,
+    crate::models::{
+        Account,
+        AccountEvent,
+        AccountOrder,
+        CreditLimits,
+        Dealer,
+        DisputePolicy,
+        DuplicatePolicy,
+        EventBroadcast,
+        FileSource,
+        Format,
+        Hook,
+        OutcomeReport,
+        ReaderSource,
+        RecordWriter,
+        RuleSet,
+        Snapshot,
+        StdinSource,
+        Storage,
+        Transaction,
+        TransactionRouter,
+        TransactionSource,
+        TransactionStream,
+        WriteAheadLog,
+        DEFAULT_CAPACITY,
+    },
+    futures_util::stream::Stream,
+    log::info,
+    std::{
+        fmt,
+        io::Read,
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::{Duration, SystemTime},
+    },
+    tokio::{sync::broadcast, task::JoinHandle},
 };
 
-/// Processing engine. Spawns the given amount of threads for processing.
+/// File name that makes [Engine::run] read from stdin.
+const STDIN: &str = "-";
+
+/// Reason why the engine could not process the input.
+#[derive(Debug)]
+pub enum EngineError {
+    /// A file could not be read or written.
+    Io(std::io::Error),
+    /// The accounts could not be written.
+    Output(std::io::Error),
+    /// The state of a previous run could not be recovered.
+    Recovery(String),
+    /// A dealer stopped unexpectedly.
+    Dealer(String),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Output(e) => write!(f, "Output could not be written: {}", e),
+            Self::Recovery(msg) => write!(f, "{}", msg),
+            Self::Dealer(msg) => write!(f, "Dealer stopped: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<std::io::Error> for EngineError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Everything that is required while the dealers are working.
+struct Session {
+    router: TransactionRouter,
+    storages: Vec<Arc<Storage>>,
+    handles: Vec<JoinHandle<()>>,
+    write_ahead_log: Option<Arc<WriteAheadLog>>,
+    outcome_report: Option<Arc<OutcomeReport>>,
+}
+
+/// Processing engine. Spawns the given amount of dealers for processing,
+/// every dealer is responsible for its own share of the clients.
 pub struct Engine {
+    /// Count of dealers that process transactions.
     pub thread_count: u8,
+    /// Count of transactions every dealer queues up before reading the input
+    /// is paused.
+    pub queue_capacity: usize,
+    /// Defines how transactions with an already seen tx are handled.
+    pub duplicate_policy: DuplicatePolicy,
+    /// Defines which transactions can be disputed and how disputed
+    /// withdrawals are handled.
+    pub dispute_policy: DisputePolicy,
+    /// Time after which authorizations expire on their own. If not set,
+    /// authorizations only expire by an expire row.
+    pub hold_ttl: Option<Duration>,
+    /// Directory of the write-ahead log. If set, the state of previous runs
+    /// is recovered from the log and every processed transaction is appended.
+    pub write_ahead_log: Option<PathBuf>,
+    /// Snapshot file the state is loaded from before processing.
+    pub snapshot: Option<PathBuf>,
+    /// Snapshot file the state is saved to after processing. If a
+    /// write-ahead log is used, it is cleared once the snapshot has been
+    /// saved, so the snapshot becomes the checkpoint to resume from.
+    pub save_snapshot: Option<PathBuf>,
+    /// Accounts file the credit limits are loaded from. The limits replace
+    /// the ones of a loaded snapshot, accounts that are not listed have no
+    /// credit limit.
+    pub credit_limits: Option<PathBuf>,
+    /// JSON file of the rules every transaction is checked against before it
+    /// is applied, see [RuleSet].
+    pub rules: Option<PathBuf>,
+    /// File the outcome of every input transaction is written to, including
+    /// the reason of every rejection.
+    pub outcome_report: Option<PathBuf>,
+    /// Format of the outcome report. Determined by the file extension if not
+    /// set.
+    pub outcome_report_format: Option<Format>,
+    /// Format of the input. Determined by the file extension if not set,
+    /// stdin is read as csv.
+    pub input_format: Option<Format>,
+    /// Format of the accounts that are written by [Engine::run].
+    pub output_format: Format,
+    /// Order of the returned accounts.
+    pub account_order: AccountOrder,
+    /// Creates the storage of every dealer, the index of the dealer is
+    /// passed.
+    storage_factory: Box<dyn Fn(usize) -> Storage + Send + Sync>,
+    /// Hooks that are called around every processed transaction.
+    hooks: Vec<Arc<dyn Hook>>,
+    /// Publishes the events of the accounts, created by the first
+    /// subscriber.
+    events: Option<Arc<EventBroadcast>>,
 }
 
 impl Engine {
     /// Creates a new instance.
     pub fn new(thread_count: u8) -> Self {
         Self {
-            thread_count
+            thread_count,
+            queue_capacity: DEFAULT_CAPACITY,
+            duplicate_policy: DuplicatePolicy::default(),
+            dispute_policy: DisputePolicy::default(),
+            hold_ttl: None,
+            write_ahead_log: None,
+            snapshot: None,
+            save_snapshot: None,
+            credit_limits: None,
+            rules: None,
+            outcome_report: None,
+            outcome_report_format: None,
+            input_format: None,
+            output_format: Format::Csv,
+            account_order: AccountOrder::default(),
+            storage_factory: Box::new(|_| Storage::new()),
+            hooks: vec![],
+            events: None,
+        }
+    }
+
+    /// Sets the count of transactions every dealer queues up.
+    pub fn with_queue_capacity(mut self, queue_capacity: usize) -> Self {
+        self.queue_capacity = queue_capacity;
+        self
+    }
+
+    /// Sets the policy for transactions with an already seen tx.
+    pub fn with_duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = policy;
+        self
+    }
+
+    /// Sets the policy for disputes.
+    pub fn with_dispute_policy(mut self, policy: DisputePolicy) -> Self {
+        self.dispute_policy = policy;
+        self
+    }
+
+    /// Sets the time after which authorizations expire on their own, see
+    /// [Dealer::with_hold_ttl].
+    pub fn with_hold_ttl(mut self, ttl: Duration) -> Self {
+        self.hold_ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the directory of the write-ahead log.
+    pub fn with_write_ahead_log<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.write_ahead_log = Some(dir.into());
+        self
+    }
+
+    /// Sets the snapshot file the state is loaded from before processing.
+    /// When used together with a write-ahead log, the log is replayed on top
+    /// of the snapshot.
+    pub fn with_snapshot<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.snapshot = Some(path.into());
+        self
+    }
+
+    /// Sets the snapshot file the state is saved to after processing.
+    pub fn with_save_snapshot<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.save_snapshot = Some(path.into());
+        self
+    }
+
+    /// Sets the JSON file the rules are loaded from.
+    pub fn with_rules<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.rules = Some(path.into());
+        self
+    }
+
+    /// Sets the accounts file the credit limits are loaded from, see
+    /// [CreditLimits].
+    pub fn with_credit_limits<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.credit_limits = Some(path.into());
+        self
+    }
+
+    /// Sets the file the outcome of every input transaction is written to.
+    pub fn with_outcome_report<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.outcome_report = Some(path.into());
+        self
+    }
+
+    /// Sets the format of the outcome report regardless of the file
+    /// extension.
+    pub fn with_outcome_report_format(mut self, format: Format) -> Self {
+        self.outcome_report_format = Some(format);
+        self
+    }
+
+    /// Sets the format of the input regardless of the file extension.
+    pub fn with_input_format(mut self, format: Format) -> Self {
+        self.input_format = Some(format);
+        self
+    }
+
+    /// Sets the format of the accounts that are written by [Engine::run].
+    pub fn with_output_format(mut self, format: Format) -> Self {
+        self.output_format = format;
+        self
+    }
+
+    /// Sets the order of the returned accounts.
+    pub fn with_account_order(mut self, order: AccountOrder) -> Self {
+        self.account_order = order;
+        self
+    }
+
+    /// Sets the function that creates the storage backends of every dealer.
+    /// The index of the dealer is passed, every dealer gets its own storage.
+    /// Defaults to [Storage::new].
+    pub fn with_storage<F>(mut self, factory: F) -> Self
+    where
+        F: Fn(usize) -> Storage + Send + Sync + 'static,
+    {
+        self.storage_factory = Box::new(factory);
+        self
+    }
+
+    /// Adds a hook that is called around every processed transaction, see
+    /// [Hook]. Hooks are called in the order they have been added.
+    pub fn with_hook(mut self, hook: Arc<dyn Hook>) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    /// Returns a receiver of the [AccountEvent]s that are published while
+    /// the transactions are processed. Subscribers that fall behind by more
+    /// than [DEFAULT_EVENT_CAPACITY](crate::models::DEFAULT_EVENT_CAPACITY)
+    /// events miss the oldest ones.
+    /// # Example
+    /// ```rust
+    /// use athanasia::{models::*, Engine};
+    /// let mut engine = Engine::new(1);
+    /// let mut events = engine.subscribe();
+    /// let transactions = futures::stream::iter(vec![
+    ///     Transaction::new(TransactionType::Deposit, 2, 1, "3".parse().ok()),
+    ///     Transaction::new(TransactionType::Dispute, 2, 1, None),
+    /// ]);
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// rt.block_on(engine.process_stream(transactions)).unwrap();
+    /// assert!(matches!(
+    ///     events.try_recv(),
+    ///     Ok(AccountEvent::BalanceChanged { tx: 1, .. })
+    /// ));
+    /// assert!(matches!(
+    ///     events.try_recv(),
+    ///     Ok(AccountEvent::BalanceChanged { tx: 1, .. })
+    /// ));
+    /// assert_eq!(
+    ///     events.try_recv(),
+    ///     Ok(AccountEvent::DisputeOpened { client: 2, tx: 1 })
+    /// );
+    /// ```
+    pub fn subscribe(&mut self) -> broadcast::Receiver<AccountEvent> {
+        match &self.events {
+            Some(events) => events.subscribe(),
+            None => {
+                let events = Arc::new(EventBroadcast::default());
+                self.hooks.push(events.clone());
+                let receiver = events.subscribe();
+                self.events = Some(events);
+                receiver
+            }
         }
     }
 
-    /// Makes the engine process the file.
-    pub fn run(&self, file_name: &str) {
-        let s = Arc::new(Mutex::new(TransactionStream::new()));
-        let storage = Arc::new(Storage::new());
+    /// Makes the engine process the file and writes the resulting accounts
+    /// as csv to stdout. The input is read from stdin if the file name is
+    /// `-`. Creates its own runtime, use
+    /// [process_source](Self::process_source) to run inside of an existing
+    /// one.
+    pub fn run(&self, file_name: &str) -> Result<(), EngineError> {
         let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(self.thread_count.max(1) as usize + 1)
             .enable_all()
-            .build()
-            .unwrap();
-        let thread_count = self.thread_count + 1;
-        rt.block_on(async {
-            let mut handles = vec![];
-            for i in 0..thread_count.into() {
-                let transaction_stream = s.clone();
-                let storage_clone = storage.clone();
-                let h = tokio::spawn(async move {
-                    let mut d = Dealer::new(
-                        i,
-                        storage_clone,
-                        transaction_stream,
-                    );
-                    d.work().await;
-                });
-                handles.push(h);
+            .build()?;
+        let accounts = match file_name {
+            STDIN => {
+                let source = StdinSource::new()
+                    .with_format(self.input_format.unwrap_or_default());
+                rt.block_on(self.process_source(source))?
+            }
+            _ => rt.block_on(self.process_file(file_name))?,
+        };
+        let stdout = std::io::BufWriter::new(std::io::stdout());
+        let mut writer = RecordWriter::new(self.output_format, stdout);
+        for account in &accounts {
+            writer.write(account).map_err(EngineError::Output)?;
+        }
+        writer.finish().map_err(EngineError::Output)
+    }
+
+    /// Processes the given file and returns the resulting accounts.
+    pub async fn process_file<P: Into<PathBuf>>(
+        &self,
+        path: P,
+    ) -> Result<Vec<Account>, EngineError> {
+        let mut source = FileSource::new(path);
+        if let Some(format) = self.input_format {
+            source = source.with_format(format);
+        }
+        self.process_source(source).await
+    }
+
+    /// Processes all transactions that are read as csv from the given
+    /// reader and returns the resulting accounts. The reader is read on a
+    /// blocking thread, see [ReaderSource].
+    /// # Example
+    /// ```rust
+    /// use athanasia::Engine;
+    /// let input = "type,client,tx,amount\n\
+    ///              deposit,1,1,2.5\n\
+    ///              withdrawal,1,2,1.0\n";
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// let accounts = rt
+    ///     .block_on(Engine::new(2).process_reader(input.as_bytes()))
+    ///     .unwrap();
+    /// assert_eq!(accounts.len(), 1);
+    /// assert_eq!(accounts[0].available(), "1.5".parse().unwrap());
+    /// ```
+    pub async fn process_reader<R>(
+        &self,
+        reader: R,
+    ) -> Result<Vec<Account>, EngineError>
+    where
+        R: Read + Send + 'static,
+    {
+        self.process_source(ReaderSource::new(reader)).await
+    }
+
+    /// Processes all rows of the given source and returns the resulting
+    /// accounts. Runs inside of the runtime of the caller, which needs to
+    /// provide a timer and io driver.
+    /// # Example
+    /// ```rust
+    /// use athanasia::{models::*, Engine};
+    /// let input: &[u8] = b"type,client,tx,amount\ndeposit,2,1,4.0\n";
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// let accounts = rt
+    ///     .block_on(Engine::new(1).process_source(AsyncReadSource::new(input)))
+    ///     .unwrap();
+    /// assert_eq!(accounts[0].total(), "4".parse().unwrap());
+    /// ```
+    pub async fn process_source<S: TransactionSource>(
+        &self,
+        source: S,
+    ) -> Result<Vec<Account>, EngineError> {
+        let session = self.start().await?;
+        let read = session.router.stream_from_source(source).await;
+        let accounts = self.finish(session).await?;
+        read?;
+        Ok(accounts)
+    }
+
+    /// Processes all transactions of the given stream and returns the
+    /// resulting accounts. Runs inside of the runtime of the caller.
+    /// # Example
+    /// ```rust
+    /// use athanasia::{models::*, Engine};
+    /// let amount = "3".parse().ok();
+    /// let transactions = futures::stream::iter(vec![
+    ///     Transaction::new(TransactionType::Deposit, 4, 1, amount),
+    ///     Transaction::new(TransactionType::Dispute, 4, 1, None),
+    /// ]);
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// let accounts = rt
+    ///     .block_on(Engine::new(1).process_stream(transactions))
+    ///     .unwrap();
+    /// assert_eq!(accounts[0].held(), "3".parse().unwrap());
+    /// ```
+    pub async fn process_stream<S>(
+        &self,
+        transactions: S,
+    ) -> Result<Vec<Account>, EngineError>
+    where
+        S: Stream<Item = Transaction>,
+    {
+        let session = self.start().await?;
+        session.router.stream_from(transactions).await;
+        self.finish(session).await
+    }
+
+    /// Creates the storages, restores the previous state and spawns the
+    /// dealers onto the current runtime.
+    async fn start(&self) -> Result<Session, EngineError> {
+        let outcome_report = match &self.outcome_report {
+            Some(path) => {
+                let format = self
+                    .outcome_report_format
+                    .or_else(|| Format::from_path(path))
+                    .unwrap_or_default();
+                Some(Arc::new(OutcomeReport::create(path, format)?))
             }
-            match s.lock().await.stream_from_file(file_name).await {
-                Ok(_) => (),
-                Err(msg) => error!("{}", msg.to_string()),
+            None => None,
+        };
+        let shard_count = self.thread_count.max(1) as usize;
+        let streams: Vec<Arc<TransactionStream>> = (0..shard_count)
+            .map(|_| {
+                Arc::new(TransactionStream::with_capacity(self.queue_capacity))
+            })
+            .collect();
+        let storages: Vec<Arc<Storage>> = (0..shard_count)
+            .map(|i| Arc::new((self.storage_factory)(i)))
+            .collect();
+        let mut router = TransactionRouter::new(streams.clone())
+            .with_duplicate_policy(self.duplicate_policy);
+        if let Some(report) = &outcome_report {
+            router = router.with_outcome_report(report.clone());
+        }
+        let rules = match &self.rules {
+            Some(path) => Some(Arc::new(RuleSet::load(path)?)),
+            None => None,
+        };
+        let credit_limits = match &self.credit_limits {
+            Some(path) => {
+                let limits = CreditLimits::load(path)?;
+                info!(
+                    "Loaded credit limits of {} accounts from {}",
+                    limits.len(),
+                    path.display()
+                );
+                Some(Arc::new(limits))
             }
-            futures::future::join_all(handles).await;
+            None => None,
+        };
+        let mut dealers: Vec<Dealer> = streams
+            .into_iter()
+            .zip(storages.iter().cloned())
+            .enumerate()
+            .map(|(i, (transaction_stream, storage))| {
+                let dealer = Dealer::new(i as u32, storage, transaction_stream)
+                    .with_shards(storages.clone())
+                    .with_dispute_policy(self.dispute_policy);
+                let dealer = match &rules {
+                    Some(rules) => dealer.with_rules(rules.clone()),
+                    None => dealer,
+                };
+                let dealer = match &credit_limits {
+                    Some(limits) => dealer.with_credit_limits(limits.clone()),
+                    None => dealer,
+                };
+                let dealer = match self.hold_ttl {
+                    Some(ttl) => dealer.with_hold_ttl(ttl),
+                    None => dealer,
+                };
+                let dealer = self
+                    .hooks
+                    .iter()
+                    .fold(dealer, |d, hook| d.with_hook(hook.clone()));
+                match &outcome_report {
+                    Some(report) => dealer.with_outcome_report(report.clone()),
+                    None => dealer,
+                }
+            })
+            .collect();
+        if let Some(path) = &self.snapshot {
+            Self::restore_snapshot(path, &storages, &router).await?;
+        }
+        let mut write_ahead_log = None;
+        if let Some(dir) = &self.write_ahead_log {
+            let log = Arc::new(
+                Self::recover(dir, &dealers, &router, rules.as_deref()).await?,
+            );
+            dealers = dealers
+                .into_iter()
+                .map(|d| d.with_write_ahead_log(log.clone()))
+                .collect();
+            write_ahead_log = Some(log);
+        }
+        // the recovered accounts get the limits of this run
+        if let Some(limits) = &credit_limits {
+            limits.apply(&storages).await;
+        }
 
-            let mut wtr = csv::Writer::from_writer(std::io::stdout());
-            let accounts = &*storage.accounts.lock().await;
-            for (_, val) in accounts.get_map().iter() {
-                wtr.serialize(val).unwrap();
+        let handles = dealers
+            .into_iter()
+            .map(|mut d| {
+                tokio::spawn(async move {
+                    d.work().await;
+                })
+            })
+            .collect();
+        Ok(Session {
+            router,
+            storages,
+            handles,
+            write_ahead_log,
+            outcome_report,
+        })
+    }
+
+    /// Waits until the dealers have processed all routed transactions,
+    /// persists the state if requested and returns all accounts in the
+    /// configured [AccountOrder].
+    async fn finish(&self, session: Session) -> Result<Vec<Account>, EngineError> {
+        // makes sure the dealers stop, even if routing has been aborted
+        session.router.close();
+        for result in futures::future::join_all(session.handles).await {
+            result.map_err(|e| EngineError::Dealer(e.to_string()))?;
+        }
+        if let Some(report) = &session.outcome_report {
+            report.finish()?;
+        }
+        if let Some(log) = &session.write_ahead_log {
+            log.sync()?;
+        }
+        if let Some(path) = &self.save_snapshot {
+            Snapshot::capture(&session.storages).await.save(path)?;
+            // the snapshot is the new checkpoint to resume from
+            if let Some(log) = &session.write_ahead_log {
+                log.truncate()?;
             }
-            wtr.flush().unwrap();
-        });
+        }
+
+        let mut accounts = vec![];
+        for storage in &session.storages {
+            let storage = storage.accounts.lock().await;
+            accounts.extend(storage.accounts().cloned());
+        }
+        self.account_order.sort(&mut accounts);
+        Ok(accounts)
+    }
+
+    /// Loads the snapshot from the given file into the storages.
+    async fn restore_snapshot(
+        path: &Path,
+        storages: &[Arc<Storage>],
+        router: &TransactionRouter,
+    ) -> Result<(), std::io::Error> {
+        let snapshot = Snapshot::load(path)?;
+        snapshot.restore(storages, |c| router.shard(c)).await?;
+        for t in snapshot.transactions() {
+            router.mark_seen(t);
+        }
+        info!(
+            "Restored {} accounts from snapshot {}",
+            snapshot.accounts().len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Opens the write-ahead log in the given directory and replays all of
+    /// its entries, so the storages of the dealers contain the state of the
+    /// previous runs. Fails if an entry does not lead to the logged outcome.
+    /// Rejected entries are not replayed, their tx is only marked as seen.
+    /// The entries are not checked against the rules, they have been accepted
+    /// before, but count towards the limits of the rules at the time they have
+    /// been processed. Every entry is replayed with the settings it has been
+    /// processed with, see [Dealer::replay], so changed settings of the
+    /// engine only apply to the transactions after the recovery.
+    async fn recover(
+        dir: &Path,
+        dealers: &[Dealer],
+        router: &TransactionRouter,
+        rules: Option<&RuleSet>,
+    ) -> Result<WriteAheadLog, EngineError> {
+        let log = WriteAheadLog::open(dir)?;
+        let mut count = 0;
+        for entry in log.entries()? {
+            let entry = entry?;
+            let tx = entry.transaction.tx();
+            router.mark_seen(&entry.transaction);
+            let outcome = match entry.outcome {
+                Some(outcome) => outcome,
+                None => continue,
+            };
+            let dealer = &dealers[router.shard(&entry.transaction.client())];
+            if let Some(rules) = rules {
+                // logs without the time count at the time of the replay
+                let processed_at =
+                    entry.processed_at.unwrap_or_else(SystemTime::now);
+                rules.record(&entry.transaction, processed_at);
+            }
+            let replayed = dealer.replay(entry).await;
+            if replayed != Ok(outcome) {
+                return Err(EngineError::Recovery(format!(
+                    "Write-ahead log entry of transaction {} could not be replayed: {:?}",
+                    tx, replayed
+                )));
+            }
+            count += 1;
+        }
+        info!("Replayed {} transactions from the write-ahead log", count);
+        Ok(log)
     }
 }
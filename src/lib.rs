@@ -1,4 +1,5 @@
-#[warn(missing_docs)]
+//! Athanasia, an experimental, multi-threaded transaction processing engine.
+#![warn(missing_docs)]
 #[macro_use]
 extern crate serde;
 
@@ -7,4 +8,4 @@ pub mod models;
 
 mod engine;
 
-pub use engine::Engine;
+pub use engine::{Engine, EngineError};
@@ -1,7 +1,12 @@
 use {
-    athanasia::Engine,
+    athanasia::{
+        models::{AccountOrder, DisputePolicy, DuplicatePolicy, Format},
+        Engine,
+    },
     clap::{crate_authors, crate_version, Clap},
     flexi_logger::Logger,
+    log::error,
+    std::time::Duration,
 };
 
 #[derive(Clap, Debug, Clone)]
@@ -10,10 +15,65 @@ use {
     author = crate_authors!(", ")
 )]
 pub struct Config {
+    /// The csv file containing the transactions, use - to read from stdin.
     input_file: String,
     /// Count of threads that should be used for processing, limited to 8.
     #[clap(short)]
     pub thread_count: Option<u8>,
+    /// Count of transactions queued per thread before reading the input
+    /// pauses. Defaults to 1024.
+    #[clap(short = 'c')]
+    pub queue_capacity: Option<usize>,
+    /// Handling of transactions with an already seen tx. Possible values:
+    /// reject, ignore. Defaults to reject.
+    #[clap(short)]
+    pub duplicates: Option<DuplicatePolicy>,
+    /// Transactions that can be disputed. Possible values:
+    /// reverse-withdrawals, deposits-only. Defaults to reverse-withdrawals.
+    #[clap(long)]
+    pub disputes: Option<DisputePolicy>,
+    /// Seconds after which authorizations expire on their own. If not set,
+    /// authorizations only expire by an expire row.
+    #[clap(long)]
+    pub hold_ttl: Option<u64>,
+    /// Directory of the write-ahead log. The state of previous runs is
+    /// recovered from it, every processed transaction is appended.
+    #[clap(short)]
+    pub write_ahead_log: Option<String>,
+    /// Snapshot file the state is loaded from before processing.
+    #[clap(short = 's', long)]
+    pub snapshot: Option<String>,
+    /// Snapshot file the state is saved to after processing.
+    #[clap(short = 'S', long)]
+    pub save_snapshot: Option<String>,
+    /// Accounts file with the columns client and credit_limit, the available
+    /// amount of the listed accounts may go below zero up to their limit.
+    #[clap(short = 'a', long)]
+    pub accounts: Option<String>,
+    /// JSON file of rules every transaction is checked against before it is
+    /// applied, eg. maximum amounts or velocity checks.
+    #[clap(long)]
+    pub rules: Option<String>,
+    /// File the outcome of every input transaction is written to, together
+    /// with the reason of every rejection.
+    #[clap(short = 'r', long)]
+    pub outcome_report: Option<String>,
+    /// Format of the outcome report. Possible values: csv, json, ndjson.
+    /// Determined by the file extension if not set, csv if unknown.
+    #[clap(long)]
+    pub report_format: Option<Format>,
+    /// Format of the input. Possible values: csv, ndjson. Determined by the
+    /// file extension if not set, csv if unknown.
+    #[clap(short = 'f', long)]
+    pub input_format: Option<Format>,
+    /// Format of the accounts written to stdout. Possible values: csv, json,
+    /// ndjson. Defaults to csv.
+    #[clap(short = 'o', long)]
+    pub output_format: Option<Format>,
+    /// Order of the accounts. Possible values: client, client-desc,
+    /// total-desc, none. Defaults to client.
+    #[clap(long)]
+    pub order: Option<AccountOrder>,
     /// Possible values: info, debug, error, warn. Fallback: info.
     #[clap(short)]
     pub log_level: Option<String>,
@@ -32,10 +92,10 @@ fn initialize_config() -> Config {
         None => Some(1),
     };
     config.log_level = match config.log_level {
-        None => Some(format!("info")),
+        None => Some("info".to_string()),
         Some(v) => {
             if !["info", "debug", "error", "warn"].contains(&&v[..]) {
-                Some(format!("info"))
+                Some("info".to_string())
             } else {
                 Some(v)
             }
@@ -52,5 +112,52 @@ fn main() {
         .start()
         .unwrap();
 
-    Engine::new(config.thread_count.unwrap()).run(&config.input_file);
+    let mut engine = Engine::new(config.thread_count.unwrap());
+    if let Some(capacity) = config.queue_capacity {
+        engine = engine.with_queue_capacity(capacity);
+    }
+    if let Some(policy) = config.duplicates {
+        engine = engine.with_duplicate_policy(policy);
+    }
+    if let Some(policy) = config.disputes {
+        engine = engine.with_dispute_policy(policy);
+    }
+    if let Some(secs) = config.hold_ttl {
+        engine = engine.with_hold_ttl(Duration::from_secs(secs));
+    }
+    if let Some(dir) = config.write_ahead_log {
+        engine = engine.with_write_ahead_log(dir);
+    }
+    if let Some(path) = config.snapshot {
+        engine = engine.with_snapshot(path);
+    }
+    if let Some(path) = config.save_snapshot {
+        engine = engine.with_save_snapshot(path);
+    }
+    if let Some(path) = config.accounts {
+        engine = engine.with_credit_limits(path);
+    }
+    if let Some(path) = config.rules {
+        engine = engine.with_rules(path);
+    }
+    if let Some(path) = config.outcome_report {
+        engine = engine.with_outcome_report(path);
+    }
+    if let Some(format) = config.report_format {
+        engine = engine.with_outcome_report_format(format);
+    }
+    if let Some(format) = config.input_format {
+        engine = engine.with_input_format(format);
+    }
+    if let Some(format) = config.output_format {
+        engine = engine.with_output_format(format);
+    }
+    if let Some(order) = config.order {
+        engine = engine.with_account_order(order);
+    }
+    if let Err(e) = engine.run(&config.input_file) {
+        error!("{}", e);
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
@@ -4,24 +4,107 @@ mod transaction;
 /// Definition of a transaction stream that is required by the dealer to work with.
 mod transaction_stream;
 
+/// Exact fixed-point money type.
+mod amount;
+
+/// Validation of incoming rows before they are processed.
+mod validation;
+
+/// Input and output formats.
+mod format;
+
+/// Sources the transactions are read from.
+mod transaction_source;
+
+/// Distribution of incoming transactions onto the streams of the dealers.
+mod transaction_router;
+
 /// Account related definitions and implementations.
 mod account;
 
+/// Credit limits of the accounts.
+mod credit_limits;
+
+/// Dispute lifecycle of stored transactions.
+mod dispute;
+
+/// Authorization holds of reserved funds.
+mod hold;
+
 /// Storage model that holds accounts and transactions.
 mod storage;
 
+/// Outcomes and rejection reasons of processed transactions.
+mod processing;
+
+/// Default storage backends that keep everything in memory.
+mod memory_storage;
+
+/// Configurable rules checked before transactions are applied.
+mod rules;
+
+/// Durable log of accepted transactions.
+mod write_ahead_log;
+
+/// Versioned snapshots of the full state.
+mod snapshot;
+
+/// Report of the outcome of every input transaction.
+mod outcome_report;
+
+/// Hooks around the processing of transactions.
+mod hook;
+
+/// Events published while accounts change.
+mod event;
+
 /// The dealer can be bound to a storage so that its able to process transactions.
 mod dealer;
 
 pub use {
-    account::{Account, AccountId},
+    account::{Account, AccountId, AccountOrder, AccountState, StateChange},
+    amount::{AmountError, CreditAmount, DECIMAL_PLACES},
+    credit_limits::CreditLimits,
     dealer::Dealer,
-    storage::{AccountStorage, DisputeRegister, Storage, TransactionStorage},
+    dispute::{Dispute, DisputePolicy, DisputeState},
+    event::{AccountEvent, EventBroadcast, DEFAULT_EVENT_CAPACITY},
+    format::{Format, RecordWriter},
+    hold::{Hold, HoldState},
+    hook::{AccountChange, Hook},
+    memory_storage::{
+        InMemoryAccountStorage,
+        InMemoryDisputeRegister,
+        InMemoryHoldRegister,
+        InMemoryTransactionStorage,
+    },
+    outcome_report::OutcomeReport,
+    processing::{Outcome, ProcessingError},
+    rules::{Rule, RuleKind, RuleSet},
+    snapshot::{Snapshot, SNAPSHOT_VERSION},
+    storage::{
+        AccountStorage,
+        DisputeRegister,
+        HoldRegister,
+        Storage,
+        TransactionStorage,
+    },
     transaction::{
         Transaction,
         TransactionId,
-        TransactionQueue,
         TransactionType,
     },
-    transaction_stream::TransactionStream,
+    transaction_router::{DuplicatePolicy, TransactionRouter},
+    transaction_source::{
+        AsyncReadSource,
+        FileSource,
+        IteratorSource,
+        ReaderSource,
+        RejectedRow,
+        SourceRow,
+        StdinSource,
+        TransactionSource,
+    },
+    transaction_stream::{TransactionStream, DEFAULT_CAPACITY},
+    validation::{validate, TransactionRecord, ValidationError},
+    write_ahead_log::{LogEntry, WriteAheadLog, WRITE_AHEAD_LOG_FILE},
 };
@@ -1,21 +1,240 @@
-use {log::debug, serde::Serializer};
+use {
+    crate::models::{CreditAmount, ProcessingError, TransactionType},
+    log::debug,
+    std::{cmp::Reverse, fmt, str::FromStr},
+};
 
 /// Represents an account id.
 pub type AccountId = u16;
-/// Represents the credit amount of an account.
-pub type CreditAmount = f32;
+
+/// Order of the accounts in the output. Every order except
+/// [AccountOrder::Unordered] is total, so the same state always results in
+/// the same output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AccountOrder {
+    /// Ascending by client id.
+    #[default]
+    Client,
+    /// Descending by client id.
+    ClientDescending,
+    /// Descending by total amount, accounts with the same total ascending by
+    /// client id.
+    TotalDescending,
+    /// In the order of the storages, which changes from run to run.
+    Unordered,
+}
+
+impl AccountOrder {
+    /// Sorts the given accounts.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::{Account, AccountOrder};
+    /// let mut accounts = vec![Account::new(3), Account::new(1), Account::new(2)];
+    /// AccountOrder::Client.sort(&mut accounts);
+    /// let ids: Vec<_> = accounts.iter().map(Account::id).collect();
+    /// assert_eq!(ids, vec![1, 2, 3]);
+    /// ```
+    pub fn sort(self, accounts: &mut [Account]) {
+        match self {
+            Self::Client => accounts.sort_unstable_by_key(|a| a.id),
+            Self::ClientDescending => {
+                accounts.sort_unstable_by_key(|a| Reverse(a.id))
+            }
+            Self::TotalDescending => {
+                accounts.sort_unstable_by_key(|a| (Reverse(a.total), a.id))
+            }
+            Self::Unordered => (),
+        }
+    }
+}
+
+impl FromStr for AccountOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "client" => Ok(Self::Client),
+            "client-desc" => Ok(Self::ClientDescending),
+            "total-desc" => Ok(Self::TotalDescending),
+            "none" => Ok(Self::Unordered),
+            _ => Err(format!("Unknown account order: {}", s)),
+        }
+    }
+}
+
+/// Lifecycle state of an account, defines which transactions it accepts.
+///
+/// A chargeback locks the account, all other changes are made by the
+/// administrative transaction types: `Active <-> Frozen` by a freeze and an
+/// unfreeze, `Locked -> Active` by an unlock and any state except `Closed`
+/// to `Closed` by a close. An account that has been frozen when it was
+/// locked returns to `Frozen` on an unlock, see [Account::next_state]. A
+/// locked account still settles the disputes and authorizations that are
+/// open when it has been locked.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountState {
+    /// Accepts all transactions.
+    #[default]
+    Active,
+    /// Accepts deposits, incoming transfers, the dispute transactions and
+    /// the ones settling existing authorizations, but no new credit can be
+    /// taken from the account.
+    Frozen,
+    /// Locked by a chargeback, only accepts the resolves and chargebacks
+    /// that settle disputes which are already open, and the captures, voids
+    /// and expiries of open authorizations.
+    Locked,
+    /// Closed for good, does not accept any transactions. Only accounts
+    /// without open authorizations and disputes can be closed.
+    Closed,
+}
+
+impl fmt::Display for AccountState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = match self {
+            Self::Active => "active",
+            Self::Frozen => "frozen",
+            Self::Locked => "locked",
+            Self::Closed => "closed",
+        };
+        write!(f, "{}", v)
+    }
+}
+
+impl AccountState {
+    /// Checks if an account in this state accepts the given transaction
+    /// type from its client.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::{AccountState, ProcessingError, TransactionType};
+    /// let state = AccountState::Frozen;
+    /// assert_eq!(state.check(&TransactionType::Deposit), Ok(()));
+    /// assert_eq!(
+    ///     state.check(&TransactionType::Withdrawal),
+    ///     Err(ProcessingError::NotAccepted {
+    ///         state,
+    ///         transaction_type: TransactionType::Withdrawal,
+    ///     })
+    /// );
+    /// let state = AccountState::Locked;
+    /// assert_eq!(state.check(&TransactionType::Chargeback), Ok(()));
+    /// assert_eq!(state.check(&TransactionType::Void), Ok(()));
+    /// assert_eq!(
+    ///     state.check(&TransactionType::Dispute),
+    ///     Err(ProcessingError::AccountLocked)
+    /// );
+    /// ```
+    pub fn check(
+        self,
+        transaction_type: &TransactionType,
+    ) -> Result<(), ProcessingError> {
+        if transaction_type.is_admin() {
+            return self.next(transaction_type).map(|_| ());
+        }
+        match (self, transaction_type) {
+            (Self::Active, _) => Ok(()),
+            (Self::Frozen, TransactionType::Withdrawal)
+            | (Self::Frozen, TransactionType::Transfer)
+            | (Self::Frozen, TransactionType::Authorize) => {
+                Err(self.rejection(transaction_type))
+            }
+            (Self::Frozen, _) => Ok(()),
+            // only open disputes and authorizations can be settled, which is
+            // checked by the dispute or the hold itself
+            (Self::Locked, TransactionType::Resolve)
+            | (Self::Locked, TransactionType::Chargeback)
+            | (Self::Locked, TransactionType::Capture)
+            | (Self::Locked, TransactionType::Void)
+            | (Self::Locked, TransactionType::Expire) => Ok(()),
+            (Self::Locked, _) => Err(ProcessingError::AccountLocked),
+            (Self::Closed, _) => Err(self.rejection(transaction_type)),
+        }
+    }
+
+    /// Returns true if an account in this state accepts the given
+    /// transaction type as recipient, ie. a transfer from another client or
+    /// a dispute transaction of such a transfer.
+    pub fn accepts_transfers(self, transaction_type: &TransactionType) -> bool {
+        matches!(
+            (self, transaction_type),
+            (Self::Active, _)
+                | (Self::Frozen, _)
+                | (Self::Locked, TransactionType::Resolve)
+                | (Self::Locked, TransactionType::Chargeback)
+        )
+    }
+
+    /// Returns the state that the given administrative transaction type
+    /// leads to, or an error if the transition is not allowed.
+    pub fn next(
+        self,
+        transaction_type: &TransactionType,
+    ) -> Result<Self, ProcessingError> {
+        match (self, transaction_type) {
+            (Self::Active, TransactionType::Freeze) => Ok(Self::Frozen),
+            (Self::Frozen, TransactionType::Unfreeze) => Ok(Self::Active),
+            (Self::Locked, TransactionType::Unlock) => Ok(Self::Active),
+            (Self::Closed, TransactionType::Close) => {
+                Err(self.rejection(transaction_type))
+            }
+            (_, TransactionType::Close) => Ok(Self::Closed),
+            (_, t) => Err(self.rejection(t)),
+        }
+    }
+
+    fn rejection(self, transaction_type: &TransactionType) -> ProcessingError {
+        ProcessingError::NotAccepted {
+            state: self,
+            transaction_type: *transaction_type,
+        }
+    }
+}
+
+/// A change of the [AccountState] together with its reason.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateChange {
+    from: AccountState,
+    to: AccountState,
+    reason: String,
+}
+
+impl StateChange {
+    /// Returns the state before the change.
+    pub fn from(&self) -> AccountState {
+        self.from
+    }
+
+    /// Returns the state after the change.
+    pub fn to(&self) -> AccountState {
+        self.to
+    }
+
+    /// Returns the reason of the change.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
 
 /// Represents a clients account.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Account {
     id: AccountId,
-    #[serde(serialize_with = "serialize_amount")]
     available: CreditAmount,
-    #[serde(serialize_with = "serialize_amount")]
     held: CreditAmount,
-    #[serde(serialize_with = "serialize_amount")]
     total: CreditAmount,
     locked: bool,
+    /// Not part of the output, restored from the history, see
+    /// [Account::state].
+    #[serde(skip)]
+    state: AccountState,
+    #[serde(default)]
+    credit_limit: CreditAmount,
+    /// Not part of the output, see [Account::history].
+    #[serde(skip)]
+    history: Vec<StateChange>,
 }
 
 impl Account {
@@ -23,10 +242,13 @@ impl Account {
     pub fn new(id: u16) -> Self {
         Self {
             id,
-            available: 0.0,
-            held: 0.0,
-            total: 0.0,
+            available: CreditAmount::ZERO,
+            held: CreditAmount::ZERO,
+            total: CreditAmount::ZERO,
             locked: false,
+            state: AccountState::Active,
+            credit_limit: CreditAmount::ZERO,
+            history: vec![],
         }
     }
 
@@ -55,61 +277,314 @@ impl Account {
         self.locked
     }
 
+    /// Returns the credit limit, the amount the available amount may go
+    /// below zero.
+    pub fn credit_limit(&self) -> CreditAmount {
+        self.credit_limit
+    }
+
+    /// Sets the credit limit. Debits are accepted as long as the available
+    /// amount does not go below the negative credit limit.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::{Account, ProcessingError};
+    /// let mut account = Account::new(1);
+    /// account.set_credit_limit("5".parse().unwrap());
+    /// account.withdrawal(&"4".parse().unwrap()).unwrap();
+    /// assert_eq!(account.available(), "-4".parse().unwrap());
+    /// assert_eq!(
+    ///     account.withdrawal(&"2".parse().unwrap()),
+    ///     Err(ProcessingError::InsufficientFunds)
+    /// );
+    /// ```
+    pub fn set_credit_limit(&mut self, limit: CreditAmount) {
+        self.credit_limit = limit;
+    }
+
+    /// Returns the state of the account.
+    pub fn state(&self) -> AccountState {
+        self.state
+    }
+
+    /// Returns all state changes of the account, oldest first.
+    pub fn history(&self) -> &[StateChange] {
+        &self.history
+    }
+
+    /// Returns the state that the given administrative transaction type
+    /// leads to, or an error if the transition is not allowed. An unlock
+    /// returns to the state the account had when it has been locked, so a
+    /// freeze stays in force.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::{Account, AccountState, TransactionType};
+    /// let amount = "2".parse().unwrap();
+    /// let mut account = Account::new(1);
+    /// account.deposit(&amount).unwrap();
+    /// account.dispute(&amount).unwrap();
+    /// account.change_state(AccountState::Frozen, "fraud suspicion");
+    /// account.chargeback(&amount).unwrap();
+    /// assert_eq!(account.state(), AccountState::Locked);
+    /// assert_eq!(
+    ///     account.next_state(&TransactionType::Unlock),
+    ///     Ok(AccountState::Frozen)
+    /// );
+    /// ```
+    pub fn next_state(
+        &self,
+        transaction_type: &TransactionType,
+    ) -> Result<AccountState, ProcessingError> {
+        let state = self.state.next(transaction_type)?;
+        if *transaction_type != TransactionType::Unlock {
+            return Ok(state);
+        }
+        let locked_from = self
+            .history
+            .iter()
+            .rev()
+            .find(|c| c.to == AccountState::Locked)
+            .map(|c| c.from);
+        match locked_from {
+            Some(AccountState::Frozen) => Ok(AccountState::Frozen),
+            _ => Ok(state),
+        }
+    }
+
+    /// Changes the state of the account and records the change together
+    /// with the given reason. Setting the current state again is not
+    /// recorded.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::{Account, AccountState};
+    /// let mut account = Account::new(1);
+    /// account.change_state(AccountState::Frozen, "fraud suspicion");
+    /// assert_eq!(account.state(), AccountState::Frozen);
+    /// assert_eq!(account.history()[0].from(), AccountState::Active);
+    /// assert_eq!(account.history()[0].reason(), "fraud suspicion");
+    /// ```
+    pub fn change_state<S: Into<String>>(&mut self, state: AccountState, reason: S) {
+        if state == self.state {
+            return;
+        }
+        self.history.push(StateChange {
+            from: self.state,
+            to: state,
+            reason: reason.into(),
+        });
+        self.state = state;
+        self.locked = state == AccountState::Locked;
+        #[cfg(debug_assertions)]
+        debug!("Account id {} is {} now", self.id, state);
+    }
+
+    /// Restores a previously recorded state change, the account is in the
+    /// state the change led to afterwards.
+    pub(crate) fn restore_history(&mut self, change: StateChange) {
+        self.state = change.to;
+        self.locked = change.to == AccountState::Locked;
+        self.history.push(change);
+    }
+
     /// Executes a deposit on the account.
-    pub fn deposit(&mut self, amount: &CreditAmount) {
-        self.available += amount;
-        self.total += amount;
+    pub fn deposit(&mut self, amount: &CreditAmount) -> Result<(), ProcessingError> {
+        let available = self.checked(self.available.checked_add(*amount))?;
+        let total = self.checked(self.total.checked_add(*amount))?;
+        self.available = available;
+        self.total = total;
         #[cfg(debug_assertions)]
         debug!("Deposit {} on account id {}", amount, self.id);
+        Ok(())
     }
 
     /// Executes a withdrawal on the account.
-    pub fn withdrawal(&mut self, amount: &CreditAmount) -> Result<(), String> {
-        if self.available < *amount {
-            let msg = format!(
+    pub fn withdrawal(&mut self, amount: &CreditAmount) -> Result<(), ProcessingError> {
+        if !self.covers(amount)? {
+            #[cfg(debug_assertions)]
+            debug!(
                 "Withdrawal ignored on account id {}. Amount: {}",
                 self.id, amount
             );
-            #[cfg(debug_assertions)]
-            debug!("{}", msg);
-            return Err(msg);
+            return Err(ProcessingError::InsufficientFunds);
         }
-        self.available -= amount;
-        self.total -= amount;
+        let available = self.checked(self.available.checked_sub(*amount))?;
+        let total = self.checked(self.total.checked_sub(*amount))?;
+        self.available = available;
+        self.total = total;
         #[cfg(debug_assertions)]
         debug!("Withdrawal {} on account id {}", amount, self.id);
         Ok(())
     }
 
+    /// Reserves the given amount by moving it from the available to the
+    /// held amount.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::{Account, CreditAmount};
+    /// let amount = "2".parse().unwrap();
+    /// let mut account = Account::new(1);
+    /// account.deposit(&amount).unwrap();
+    /// account.authorize(&amount).unwrap();
+    /// assert_eq!((account.available(), account.held()), (CreditAmount::ZERO, amount));
+    /// account.capture(&amount).unwrap();
+    /// assert_eq!((account.held(), account.total()), (CreditAmount::ZERO, CreditAmount::ZERO));
+    /// ```
+    pub fn authorize(&mut self, amount: &CreditAmount) -> Result<(), ProcessingError> {
+        if !self.covers(amount)? {
+            #[cfg(debug_assertions)]
+            debug!(
+                "Authorization ignored on account id {}. Amount: {}",
+                self.id, amount
+            );
+            return Err(ProcessingError::InsufficientFunds);
+        }
+        let available = self.checked(self.available.checked_sub(*amount))?;
+        let held = self.checked(self.held.checked_add(*amount))?;
+        self.available = available;
+        self.held = held;
+        #[cfg(debug_assertions)]
+        debug!("Authorization {} on account id {}", amount, self.id);
+        Ok(())
+    }
+
+    /// Takes the given amount of reserved credit from the account.
+    pub fn capture(&mut self, amount: &CreditAmount) -> Result<(), ProcessingError> {
+        let held = self.checked(self.held.checked_sub(*amount))?;
+        let total = self.checked(self.total.checked_sub(*amount))?;
+        self.held = held;
+        self.total = total;
+        #[cfg(debug_assertions)]
+        debug!("Capture {} on account id {}", amount, self.id);
+        Ok(())
+    }
+
+    /// Makes the given amount of reserved credit available again.
+    pub fn release(&mut self, amount: &CreditAmount) -> Result<(), ProcessingError> {
+        let available = self.checked(self.available.checked_add(*amount))?;
+        let held = self.checked(self.held.checked_sub(*amount))?;
+        self.available = available;
+        self.held = held;
+        #[cfg(debug_assertions)]
+        debug!("Release {} on account id {}", amount, self.id);
+        Ok(())
+    }
+
     /// Executes a dispute transaction.
-    pub fn dispute(&mut self, amount: &CreditAmount) {
-        self.available -= amount;
-        self.held += amount;
+    pub fn dispute(&mut self, amount: &CreditAmount) -> Result<(), ProcessingError> {
+        let available = self.checked(self.available.checked_sub(*amount))?;
+        let held = self.checked(self.held.checked_add(*amount))?;
+        self.available = available;
+        self.held = held;
         #[cfg(debug_assertions)]
         debug!("Dispute {} on account id {}", amount, self.id);
+        Ok(())
     }
 
     /// Executes a resolve transaction.
-    pub fn resolve(&mut self, amount: &CreditAmount) {
-        self.available += amount;
-        self.held -= amount;
+    pub fn resolve(&mut self, amount: &CreditAmount) -> Result<(), ProcessingError> {
+        let available = self.checked(self.available.checked_add(*amount))?;
+        let held = self.checked(self.held.checked_sub(*amount))?;
+        self.available = available;
+        self.held = held;
         #[cfg(debug_assertions)]
         debug!("Resolve {} on account id {}", amount, self.id);
+        Ok(())
     }
 
     /// Executes a chargeback transaction.
-    pub fn chargeback(&mut self, amount: &CreditAmount) {
-        self.held -= amount;
-        self.total -= amount;
-        self.locked = true;
+    pub fn chargeback(&mut self, amount: &CreditAmount) -> Result<(), ProcessingError> {
+        let held = self.checked(self.held.checked_sub(*amount))?;
+        let total = self.checked(self.total.checked_sub(*amount))?;
+        self.held = held;
+        self.total = total;
+        self.lock();
         #[cfg(debug_assertions)]
         debug!("Chargeback {} on account id {}", amount, self.id);
+        Ok(())
+    }
+
+    /// Executes a dispute of a withdrawal, the withdrawn amount is credited
+    /// as held.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::{Account, CreditAmount};
+    /// let amount = "2".parse().unwrap();
+    /// let mut account = Account::new(1);
+    /// account.deposit(&amount).unwrap();
+    /// account.withdrawal(&amount).unwrap();
+    /// account.dispute_withdrawal(&amount).unwrap();
+    /// assert_eq!((account.available(), account.held()), (CreditAmount::ZERO, amount));
+    /// account.chargeback_withdrawal(&amount).unwrap();
+    /// assert_eq!((account.available(), account.held()), (amount, CreditAmount::ZERO));
+    /// assert_eq!(account.total(), amount);
+    /// assert!(account.locked());
+    /// ```
+    pub fn dispute_withdrawal(
+        &mut self,
+        amount: &CreditAmount,
+    ) -> Result<(), ProcessingError> {
+        let held = self.checked(self.held.checked_add(*amount))?;
+        let total = self.checked(self.total.checked_add(*amount))?;
+        self.held = held;
+        self.total = total;
+        #[cfg(debug_assertions)]
+        debug!("Dispute of withdrawal {} on account id {}", amount, self.id);
+        Ok(())
+    }
+
+    /// Executes a resolve of a disputed withdrawal, the withdrawal stands and
+    /// the held amount is removed.
+    pub fn resolve_withdrawal(
+        &mut self,
+        amount: &CreditAmount,
+    ) -> Result<(), ProcessingError> {
+        let held = self.checked(self.held.checked_sub(*amount))?;
+        let total = self.checked(self.total.checked_sub(*amount))?;
+        self.held = held;
+        self.total = total;
+        #[cfg(debug_assertions)]
+        debug!("Resolve of withdrawal {} on account id {}", amount, self.id);
+        Ok(())
     }
-}
 
-fn serialize_amount<S>(x: &f32, s: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    s.serialize_str(&format!("{:.4}", x))
+    /// Executes a chargeback of a disputed withdrawal, the held amount is
+    /// made available and the account locked.
+    pub fn chargeback_withdrawal(
+        &mut self,
+        amount: &CreditAmount,
+    ) -> Result<(), ProcessingError> {
+        let held = self.checked(self.held.checked_sub(*amount))?;
+        let available = self.checked(self.available.checked_add(*amount))?;
+        self.held = held;
+        self.available = available;
+        self.lock();
+        #[cfg(debug_assertions)]
+        debug!("Chargeback of withdrawal {} on account id {}", amount, self.id);
+        Ok(())
+    }
+
+    /// Locks the account after a chargeback, if not locked yet.
+    fn lock(&mut self) {
+        if self.state != AccountState::Locked {
+            self.change_state(AccountState::Locked, "chargeback");
+        }
+    }
+
+    /// Returns true if the given amount can be debited without exceeding
+    /// the credit limit.
+    fn covers(&self, amount: &CreditAmount) -> Result<bool, ProcessingError> {
+        let funds = self.checked(self.available.checked_add(self.credit_limit))?;
+        Ok(funds >= *amount)
+    }
+
+    /// Turns the result of a checked operation into an error.
+    fn checked(
+        &self,
+        v: Option<CreditAmount>,
+    ) -> Result<CreditAmount, ProcessingError> {
+        #[cfg(debug_assertions)]
+        if v.is_none() {
+            debug!("Amount overflow on account id {}", self.id);
+        }
+        v.ok_or(ProcessingError::Overflow)
+    }
 }
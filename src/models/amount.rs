@@ -0,0 +1,221 @@
+use {
+    serde::{
+        de::{self, Visitor},
+        Deserializer,
+        Serializer,
+    },
+    std::{fmt, str::FromStr},
+};
+
+/// Count of decimal places that are represented exactly.
+pub const DECIMAL_PLACES: usize = 4;
+
+/// Factor between the smallest representable unit and one full unit.
+const SCALE: i64 = 10_000;
+
+/// Exact fixed-point representation of a monetary amount with four decimal
+/// places.
+///
+/// Internally the amount is stored as an integer count of ten-thousandths, so
+/// no precision is lost when adding up lots of transactions. All arithmetic is
+/// checked and reports an overflow instead of wrapping.
+/// # Example
+/// ```rust
+/// use athanasia::models::CreditAmount;
+/// let a: CreditAmount = "0.1".parse().unwrap();
+/// let b: CreditAmount = "0.2".parse().unwrap();
+/// let c = a.checked_add(b).unwrap();
+/// assert_eq!(c, "0.3".parse().unwrap());
+/// assert_eq!(c.to_string(), "0.3000");
+/// assert_eq!(CreditAmount::from_raw(i64::MAX).checked_add(a), None);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CreditAmount(i64);
+
+impl CreditAmount {
+    /// The amount zero.
+    pub const ZERO: Self = Self(0);
+
+    /// Creates an amount from the given count of ten-thousandths.
+    pub const fn from_raw(raw: i64) -> Self {
+        Self(raw)
+    }
+
+    /// Returns the amount as count of ten-thousandths.
+    pub const fn raw(&self) -> i64 {
+        self.0
+    }
+
+    /// Adds both amounts, returns `None` on overflow.
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Self)
+    }
+
+    /// Subtracts `rhs` from the amount, returns `None` on overflow.
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Self)
+    }
+
+    /// Returns true if the amount is lower than zero.
+    pub fn is_negative(&self) -> bool {
+        self.0 < 0
+    }
+
+    /// Returns true if the amount is zero.
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl fmt::Display for CreditAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        let scale = SCALE as u64;
+        write!(
+            f,
+            "{}{}.{:0width$}",
+            sign,
+            abs / scale,
+            abs % scale,
+            width = DECIMAL_PLACES
+        )
+    }
+}
+
+/// Error that occurs when parsing a [CreditAmount].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmountError {
+    /// The input is not a decimal number.
+    Invalid(String),
+    /// The input has more significant decimal places than supported.
+    TooPrecise(String),
+    /// The input does not fit into the value range.
+    Overflow(String),
+}
+
+impl fmt::Display for AmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(v) => write!(f, "Invalid amount: {:?}", v),
+            Self::TooPrecise(v) => write!(
+                f,
+                "Amount {:?} has more than {} decimal places",
+                v, DECIMAL_PLACES
+            ),
+            Self::Overflow(v) => write!(f, "Amount {:?} is out of range", v),
+        }
+    }
+}
+
+impl std::error::Error for AmountError {}
+
+impl FromStr for CreditAmount {
+    type Err = AmountError;
+
+    /// Parses a decimal number like `-12.3456` without going through floating
+    /// point numbers. Trailing zeros after the fourth decimal place are
+    /// accepted, any other digit there is rejected.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AmountError::Invalid(s.to_string());
+        let overflow = || AmountError::Overflow(s.to_string());
+        let input = s.trim();
+        let (negative, input) = match input.as_bytes().first() {
+            Some(b'-') => (true, &input[1..]),
+            Some(b'+') => (false, &input[1..]),
+            _ => (false, input),
+        };
+        let (int_part, frac_part) = match input.find('.') {
+            Some(i) => (&input[..i], &input[i + 1..]),
+            None => (input, ""),
+        };
+        if int_part.is_empty() && frac_part.is_empty() {
+            return Err(invalid());
+        }
+        let all_digits = |v: &str| v.bytes().all(|b| b.is_ascii_digit());
+        if !all_digits(int_part) || !all_digits(frac_part) {
+            return Err(invalid());
+        }
+        if frac_part.len() > DECIMAL_PLACES
+            && frac_part[DECIMAL_PLACES..].bytes().any(|b| b != b'0')
+        {
+            return Err(AmountError::TooPrecise(s.to_string()));
+        }
+
+        let mut raw: i64 = 0;
+        for b in int_part.bytes() {
+            raw = raw
+                .checked_mul(10)
+                .and_then(|v| v.checked_add((b - b'0') as i64))
+                .ok_or_else(overflow)?;
+        }
+        for i in 0..DECIMAL_PLACES {
+            let digit = frac_part.as_bytes().get(i).map_or(0, |b| b - b'0');
+            raw = raw
+                .checked_mul(10)
+                .and_then(|v| v.checked_add(digit as i64))
+                .ok_or_else(overflow)?;
+        }
+        Ok(Self(if negative { -raw } else { raw }))
+    }
+}
+
+impl serde::Serialize for CreditAmount {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        s.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for CreditAmount {
+    fn deserialize<D>(d: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        d.deserialize_str(CreditAmountVisitor)
+    }
+}
+
+struct CreditAmountVisitor;
+
+impl<'de> Visitor<'de> for CreditAmountVisitor {
+    type Value = CreditAmount;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a decimal number with at most {} places", DECIMAL_PLACES)
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        v.checked_mul(SCALE)
+            .map(CreditAmount)
+            .ok_or_else(|| E::custom(AmountError::Overflow(v.to_string())))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.visit_str(&v.to_string())
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        // the shortest representation of a float round-trips, so this is
+        // exact for every value that has been written with four places
+        self.visit_str(&v.to_string())
+    }
+}
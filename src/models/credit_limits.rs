@@ -0,0 +1,102 @@
+use {
+    crate::models::{Account, AccountId, CreditAmount, Storage},
+    std::{
+        collections::HashMap,
+        io::{self, Read},
+        path::Path,
+        sync::Arc,
+    },
+};
+
+/// A single row of the accounts file.
+#[derive(Deserialize, Debug)]
+struct CreditLine {
+    client: AccountId,
+    credit_limit: CreditAmount,
+}
+
+/// Approved credit limits of the accounts, read from an accounts file with
+/// the columns `client` and `credit_limit`. Accounts that are not listed
+/// have no credit limit.
+#[derive(Debug, Default)]
+pub struct CreditLimits(HashMap<AccountId, CreditAmount>);
+
+impl CreditLimits {
+    /// Reads the credit limits from the given csv file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_reader(std::fs::File::open(path)?)
+    }
+
+    /// Reads the credit limits from the given csv input. Fails if a limit is
+    /// negative or a client is listed twice.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::CreditLimits;
+    /// let input: &[u8] = b"client,credit_limit\n1,100.0\n2,25\n";
+    /// let limits = CreditLimits::from_reader(input).unwrap();
+    /// assert_eq!(limits.get(&2), "25".parse().unwrap());
+    /// assert!(limits.get(&3).is_zero());
+    /// assert!(CreditLimits::from_reader(&b"client,credit_limit\n1,-1\n"[..]).is_err());
+    /// ```
+    pub fn from_reader<R: Read>(reader: R) -> io::Result<Self> {
+        let mut limits = HashMap::new();
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        for row in reader.deserialize::<CreditLine>() {
+            let row = row?;
+            if row.credit_limit.is_negative() {
+                return Err(invalid_data(format!(
+                    "Credit limit of client {} is negative",
+                    row.client
+                )));
+            }
+            if limits.insert(row.client, row.credit_limit).is_some() {
+                return Err(invalid_data(format!(
+                    "Client {} is listed twice",
+                    row.client
+                )));
+            }
+        }
+        Ok(Self(limits))
+    }
+
+    /// Returns the credit limit of the client.
+    pub fn get(&self, client: &AccountId) -> CreditAmount {
+        self.0.get(client).copied().unwrap_or_default()
+    }
+
+    /// Returns the count of listed clients.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if no client is listed.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Replaces the credit limits of the accounts that are already stored,
+    /// eg. restored from a snapshot, so accounts that are not listed lose
+    /// their limit. No account is opened, the dealers set the limit when the
+    /// account of a listed client is opened, see
+    /// [Dealer::with_credit_limits](crate::models::Dealer::with_credit_limits).
+    pub async fn apply(&self, storages: &[Arc<Storage>]) {
+        for storage in storages {
+            let mut accounts = storage.accounts.lock().await;
+            let changed: Vec<Account> = accounts
+                .accounts()
+                .filter(|a| a.credit_limit() != self.get(&a.id()))
+                .cloned()
+                .collect();
+            for mut account in changed {
+                account.set_credit_limit(self.get(&account.id()));
+                let _ = accounts.set(account);
+            }
+        }
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
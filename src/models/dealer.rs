@@ -1,23 +1,53 @@
 use {
     crate::models::{
+        transaction_router::shard_index,
+        Account,
+        AccountChange,
+        AccountId,
+        AccountStorage,
+        CreditLimits,
+        DisputePolicy,
+        DisputeRegister,
+        DisputeState,
+        Hold,
+        Hook,
+        LogEntry,
+        Outcome,
+        OutcomeReport,
+        ProcessingError,
+        RuleSet,
         Storage,
         Transaction,
+        TransactionStorage,
         TransactionStream,
         TransactionType,
+        validate,
+        WriteAheadLog,
+    },
+    log::{debug, error, warn},
+    std::{
+        sync::Arc,
+        time::{Duration, SystemTime},
     },
-    log::{error, warn, debug},
-    std::sync::Arc,
-    tokio::sync::{Mutex},
 };
 
-const DEALER_SLEEP_MILLISECONDS: u8 = 1;
-
 /// The dealer is able to process transactions and is the primary decision maker
-/// in corner cases.
+/// in corner cases. Every dealer owns the storage shard of the clients that
+/// are routed onto its transaction stream.
 pub struct Dealer {
     id: u32,
     storage: Arc<Storage>,
-    transaction_stream: Arc<Mutex<TransactionStream>>,
+    /// Storages of all dealers by shard, used for transfers to clients of
+    /// other dealers.
+    shards: Vec<Arc<Storage>>,
+    transaction_stream: Arc<TransactionStream>,
+    write_ahead_log: Option<Arc<WriteAheadLog>>,
+    outcome_report: Option<Arc<OutcomeReport>>,
+    rules: Option<Arc<RuleSet>>,
+    credit_limits: Option<Arc<CreditLimits>>,
+    hooks: Vec<Arc<dyn Hook>>,
+    dispute_policy: DisputePolicy,
+    hold_ttl: Option<Duration>,
 }
 
 impl Dealer {
@@ -25,121 +55,845 @@ impl Dealer {
     pub fn new(
         id: u32,
         storage: Arc<Storage>,
-        transaction_stream: Arc<Mutex<TransactionStream>>,
+        transaction_stream: Arc<TransactionStream>,
     ) -> Self {
         Self {
             id,
+            shards: vec![storage.clone()],
             storage,
             transaction_stream,
+            write_ahead_log: None,
+            outcome_report: None,
+            rules: None,
+            credit_limits: None,
+            hooks: vec![],
+            dispute_policy: DisputePolicy::default(),
+            hold_ttl: None,
         }
     }
 
+    /// Makes the dealer append every accepted transaction to the given log.
+    pub fn with_write_ahead_log(mut self, log: Arc<WriteAheadLog>) -> Self {
+        self.write_ahead_log = Some(log);
+        self
+    }
+
+    /// Makes the dealer add the outcome of every processed transaction to
+    /// the given report.
+    pub fn with_outcome_report(mut self, report: Arc<OutcomeReport>) -> Self {
+        self.outcome_report = Some(report);
+        self
+    }
+
+    /// Makes the dealer check every transaction against the given rules
+    /// before it is processed.
+    pub fn with_rules(mut self, rules: Arc<RuleSet>) -> Self {
+        self.rules = Some(rules);
+        self
+    }
+
+    /// Sets the credit limits of the accounts the dealer opens.
+    /// # Example
+    /// ```rust
+    /// use {
+    ///     athanasia::models::*,
+    ///     std::sync::Arc,
+    /// };
+    /// let input: &[u8] = b"client,credit_limit\n1,5\n2,5\n";
+    /// let limits = CreditLimits::from_reader(input).unwrap();
+    /// let storage = Arc::new(Storage::new());
+    /// let dealer = Dealer::new(
+    ///     0,
+    ///     storage.clone(),
+    ///     Arc::new(TransactionStream::new()),
+    /// )
+    /// .with_credit_limits(Arc::new(limits));
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///     let t = Transaction::new(TransactionType::Withdrawal, 1, 1, "3".parse().ok());
+    ///     assert_eq!(dealer.process(t).await, Ok(Outcome::Withdrawn));
+    ///     let accounts = storage.accounts.lock().await;
+    ///     assert_eq!(accounts.get(&1).available(), "-3".parse().unwrap());
+    ///     // client 2 has no transactions, so it has no account
+    ///     assert!(accounts.find(&2).is_none());
+    /// });
+    /// ```
+    pub fn with_credit_limits(mut self, limits: Arc<CreditLimits>) -> Self {
+        self.credit_limits = Some(limits);
+        self
+    }
+
+    /// Adds a hook that is called around every processed transaction. Hooks
+    /// are called in the order they have been added.
+    pub fn with_hook(mut self, hook: Arc<dyn Hook>) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    /// Sets the transactions that can be disputed and how disputed
+    /// withdrawals are handled.
+    /// # Example
+    /// ```rust
+    /// use {
+    ///     athanasia::models::*,
+    ///     std::sync::Arc,
+    /// };
+    /// let dealer = Dealer::new(
+    ///     0,
+    ///     Arc::new(Storage::new()),
+    ///     Arc::new(TransactionStream::new()),
+    /// )
+    /// .with_dispute_policy(DisputePolicy::DepositsOnly);
+    /// let amount = "1.5".parse().ok();
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///     let t = Transaction::new(TransactionType::Deposit, 1, 1, amount);
+    ///     assert_eq!(dealer.process(t).await, Ok(Outcome::Deposited));
+    ///     let t = Transaction::new(TransactionType::Withdrawal, 1, 2, amount);
+    ///     assert_eq!(dealer.process(t).await, Ok(Outcome::Withdrawn));
+    ///     let t = Transaction::new(TransactionType::Dispute, 1, 2, None);
+    ///     assert_eq!(
+    ///         dealer.process(t).await,
+    ///         Err(ProcessingError::NotDisputable)
+    ///     );
+    /// });
+    /// ```
+    pub fn with_dispute_policy(mut self, policy: DisputePolicy) -> Self {
+        self.dispute_policy = policy;
+        self
+    }
+
+    /// Makes the authorizations expire once the given time has passed since
+    /// they have been processed. The remaining amount of an expired
+    /// authorization is released before the next transaction of its client
+    /// and at the end of [Dealer::work]. Without a time, authorizations only
+    /// expire by an [TransactionType::Expire] row.
+    /// # Example
+    /// ```rust
+    /// use {
+    ///     athanasia::models::*,
+    ///     std::{
+    ///         sync::Arc,
+    ///         time::{Duration, SystemTime},
+    ///     },
+    /// };
+    /// let storage = Arc::new(Storage::new());
+    /// let dealer = Dealer::new(
+    ///     0,
+    ///     storage.clone(),
+    ///     Arc::new(TransactionStream::new()),
+    /// )
+    /// .with_hold_ttl(Duration::from_secs(60));
+    /// let now = SystemTime::now();
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///     let t = Transaction::new(TransactionType::Deposit, 1, 1, "10".parse().ok());
+    ///     assert_eq!(dealer.process_at(t, now).await, Ok(Outcome::Deposited));
+    ///     let t = Transaction::new(TransactionType::Authorize, 1, 2, "4".parse().ok());
+    ///     assert_eq!(dealer.process_at(t, now).await, Ok(Outcome::Authorized));
+    ///     // the authorization expires before the deposit is applied
+    ///     let later = now + Duration::from_secs(60);
+    ///     let t = Transaction::new(TransactionType::Deposit, 1, 3, "1".parse().ok());
+    ///     assert_eq!(dealer.process_at(t, later).await, Ok(Outcome::Deposited));
+    ///     let account = storage.accounts.lock().await.get(&1);
+    ///     assert_eq!(account.available(), "11".parse().unwrap());
+    ///     assert!(account.held().is_zero());
+    ///     let t = Transaction::new(TransactionType::Capture, 1, 2, None);
+    ///     assert!(dealer.process_at(t, later).await.is_err());
+    /// });
+    /// ```
+    pub fn with_hold_ttl(mut self, ttl: Duration) -> Self {
+        self.hold_ttl = Some(ttl);
+        self
+    }
+
+    /// Gives the dealer access to the storages of all dealers, which is
+    /// required for transfers to clients of other dealers. The storage of a
+    /// shard must be at the index of the shard, including the own storage.
+    /// Defaults to the own storage only.
+    pub fn with_shards(mut self, storages: Vec<Arc<Storage>>) -> Self {
+        assert!(!storages.is_empty(), "At least one storage is required!");
+        self.shards = storages;
+        self
+    }
+
     /// Starts working on the given transaction stream and storage given on
     /// creation.
     pub async fn work(&mut self) {
-        loop {
-            let mut t = self.transaction_stream.lock().await;
-            if !t.alive() && t.empty().await {
-                // the stream no longer receives new values and
-                // the transactions are processed
-                break;
+        // waits without using CPU time until the next transaction arrives,
+        // stops as soon as the stream is closed and drained
+        while let Some((transaction, rendezvous)) =
+            self.transaction_stream.next_item().await
+        {
+            if let Some(r) = &rendezvous {
+                r.wait().await;
+                if !r.is_leader() {
+                    // the other dealer applies the transaction meanwhile
+                    r.wait().await;
+                    continue;
+                }
             }
-            let transaction = t.next().await;
-            if let None = transaction {
-                // Nothing to do, give CPU time to breath
-                std::thread::sleep(std::time::Duration::from_millis(DEALER_SLEEP_MILLISECONDS as u64));
-                continue;
+            self.handle(transaction).await;
+            if let Some(r) = &rendezvous {
+                r.wait().await;
             }
-            match self.process(transaction.unwrap()).await {
-                Ok(_) => (),
-                Err(msg) => error!("{}", msg.to_string()),
-            };
             #[cfg(debug_assertions)]
             debug!("Dealer {} processed transaction!", &self.id);
         }
+        // authorizations of clients without further transactions
+        let accounts = &mut self.storage.accounts.lock().await;
+        if let Err(e) =
+            self.expire_holds(accounts.as_mut(), None, SystemTime::now()).await
+        {
+            error!("Dealer {} could not expire authorizations: {}", &self.id, e);
+        }
         #[cfg(debug_assertions)]
         debug!("Dealer {} finished work!", &self.id);
     }
 
+    /// Processes the transaction, logs and reports its outcome.
+    async fn handle(&self, transaction: Transaction) {
+        let tx = transaction.tx();
+        let copy = if self.write_ahead_log.is_some()
+            || self.outcome_report.is_some()
+            || self.rules.is_some()
+            || !self.hooks.is_empty()
+        {
+            Some(transaction.clone())
+        } else {
+            None
+        };
+        // the accounts before the transaction, passed to the hooks
+        let before = match (self.hooks.is_empty(), &copy) {
+            (false, Some(t)) => self.accounts_of(t).await,
+            _ => vec![],
+        };
+        let now = SystemTime::now();
+        let result = match self.check(&transaction, now) {
+            Ok(()) => self.process_at(transaction, now).await,
+            Err(e) => Err(e),
+        };
+        match &result {
+            Ok(outcome) => {
+                if let (Some(rules), Some(t)) = (&self.rules, &copy) {
+                    rules.record(t, now);
+                }
+                if let (Some(log), Some(t)) = (&self.write_ahead_log, &copy) {
+                    let entry = self.log_entry(t, *outcome, now).await;
+                    if let Err(e) = log.append(&entry) {
+                        error!("Transaction {} not logged: {}", tx, e);
+                    }
+                }
+            },
+            Err(e) => {
+                warn!("Transaction {} ignored: {}", tx, e);
+                // the tx of a rejected transaction is still taken
+                let taken = copy
+                    .as_ref()
+                    .filter(|t| !t.transaction_type().is_reference());
+                if let (Some(log), Some(t)) = (&self.write_ahead_log, taken) {
+                    let entry = LogEntry::rejected(t.clone(), now);
+                    if let Err(e) = log.append(&entry) {
+                        error!("Transaction {} not logged: {}", tx, e);
+                    }
+                }
+            },
+        };
+        if let (Some(report), Some(t)) = (&self.outcome_report, &copy) {
+            if let Err(e) = report.record(t, &result) {
+                error!("Transaction {} not reported: {}", tx, e);
+            }
+        }
+        if let (false, Some(t)) = (self.hooks.is_empty(), &copy) {
+            let changes: Vec<_> = before
+                .into_iter()
+                .zip(self.accounts_of(t).await)
+                .map(|(before, after)| AccountChange::new(before, after))
+                .collect();
+            for hook in &self.hooks {
+                hook.after(t, &result, &changes);
+            }
+        }
+    }
+
+    /// Returns the log entry of an accepted transaction together with the
+    /// settings it has been processed with.
+    async fn log_entry(
+        &self,
+        transaction: &Transaction,
+        outcome: Outcome,
+        now: SystemTime,
+    ) -> LogEntry {
+        let credit_limit = self
+            .storage
+            .accounts
+            .lock()
+            .await
+            .find(&transaction.client())
+            .map(Account::credit_limit);
+        let expires_at = match outcome {
+            Outcome::Authorized => self
+                .storage
+                .hold_register
+                .lock()
+                .await
+                .hold(&transaction.tx())
+                .and_then(|h| h.expires_at()),
+            _ => None,
+        };
+        LogEntry {
+            transaction: transaction.clone(),
+            outcome: Some(outcome),
+            processed_at: Some(now),
+            credit_limit,
+            expires_at,
+        }
+    }
+
+    /// Returns the accounts the transaction concerns, the account of the
+    /// client first, followed by the one of the recipient for transfers and
+    /// disputes of transfers. Unknown clients get an account that is not
+    /// stored.
+    async fn accounts_of(&self, transaction: &Transaction) -> Vec<Account> {
+        let recipient = match transaction.transaction_type() {
+            TransactionType::Dispute
+            | TransactionType::Resolve
+            | TransactionType::Chargeback => self
+                .storage
+                .transactions
+                .lock()
+                .await
+                .get(&transaction.tx())
+                .and_then(|t| t.recipient()),
+            _ => transaction.recipient(),
+        };
+        let mut accounts = vec![];
+        for client in std::iter::once(transaction.client()).chain(recipient) {
+            // the storages are locked one after another, so no dealer waits
+            // for a storage while holding another one
+            let account = self
+                .storage_of(&client)
+                .accounts
+                .lock()
+                .await
+                .find(&client)
+                .cloned()
+                .unwrap_or_else(|| Account::new(client));
+            accounts.push(account);
+        }
+        accounts
+    }
+
+    /// Checks the transaction against the rules and the hooks before it is
+    /// processed.
+    fn check(
+        &self,
+        transaction: &Transaction,
+        now: SystemTime,
+    ) -> Result<(), ProcessingError> {
+        if let Some(rules) = &self.rules {
+            rules.check(transaction, now)?;
+        }
+        for hook in &self.hooks {
+            hook.before(transaction).map_err(ProcessingError::Vetoed)?;
+        }
+        Ok(())
+    }
+
     /// Executes the given transaction on the given account.
-    /// If the account is locked, the transaction will be ignored.
-    pub async fn process(&self, transaction: Transaction) -> Result<(), String> {
+    /// The transaction is checked using [validate] first, invalid ones are
+    /// rejected, as well as the ones the state of the account does not
+    /// accept.
+    /// Transfers change both accounts or none of them, disputes of a transfer
+    /// hold the amount on the account of the recipient.
+    /// Returns the outcome of the transaction, or the reason why it has been
+    /// rejected.
+    /// # Example
+    /// ```rust
+    /// use {
+    ///     athanasia::models::*,
+    ///     std::sync::Arc,
+    /// };
+    /// let dealer = Dealer::new(
+    ///     0,
+    ///     Arc::new(Storage::new()),
+    ///     Arc::new(TransactionStream::new()),
+    /// );
+    /// let amount = "1.5".parse().ok();
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///     let t = Transaction::new(TransactionType::Withdrawal, 1, 1, amount);
+    ///     assert_eq!(
+    ///         dealer.process(t).await,
+    ///         Err(ProcessingError::InsufficientFunds)
+    ///     );
+    ///     let t = Transaction::new(TransactionType::Deposit, 1, 2, amount);
+    ///     assert_eq!(dealer.process(t).await, Ok(Outcome::Deposited));
+    ///     let t = Transaction::new(TransactionType::Transfer, 1, 3, amount)
+    ///         .with_recipient(2);
+    ///     assert_eq!(dealer.process(t).await, Ok(Outcome::Transferred));
+    /// });
+    /// ```
+    pub async fn process(
+        &self,
+        transaction: Transaction,
+    ) -> Result<Outcome, ProcessingError> {
+        self.process_at(transaction, SystemTime::now()).await
+    }
+
+    /// Executes the given transaction like [Dealer::process] at the given
+    /// time, which decides about the expiry of authorizations, see
+    /// [Dealer::with_hold_ttl].
+    pub async fn process_at(
+        &self,
+        transaction: Transaction,
+        now: SystemTime,
+    ) -> Result<Outcome, ProcessingError> {
+        let expires_at = self.hold_ttl.map(|ttl| now + ttl);
+        self.execute(transaction, now, self.dispute_policy, expires_at)
+            .await
+    }
+
+    /// Replays an accepted entry of the [WriteAheadLog]. The entry is
+    /// processed with the settings that have been logged with it instead of
+    /// the current ones, so a changed setting does not change the result: at
+    /// the time it has been processed, with the credit limit the client had
+    /// and the logged expiry of an authorization. Disputes are not checked
+    /// against the dispute policy, they have been accepted before.
+    pub async fn replay(
+        &self,
+        entry: LogEntry,
+    ) -> Result<Outcome, ProcessingError> {
+        let client = entry.transaction.client();
+        if let Some(limit) = entry.credit_limit {
+            let accounts = &mut self.storage.accounts.lock().await;
+            let mut acc = self.account(accounts.as_ref(), &client);
+            if acc.credit_limit() != limit {
+                acc.set_credit_limit(limit);
+                store(accounts.as_mut(), acc)?;
+            }
+        }
+        let now = entry.processed_at.unwrap_or_else(SystemTime::now);
+        self.execute(
+            entry.transaction,
+            now,
+            DisputePolicy::ReverseWithdrawals,
+            entry.expires_at,
+        )
+        .await
+    }
+
+    /// Executes the transaction at the given time with the given settings.
+    async fn execute(
+        &self,
+        transaction: Transaction,
+        now: SystemTime,
+        dispute_policy: DisputePolicy,
+        expires_at: Option<SystemTime>,
+    ) -> Result<Outcome, ProcessingError> {
+        validate(&transaction)?;
+
         let account_storage = &mut self.storage.accounts.lock().await;
-        let acc = account_storage.get(&transaction.client());
+        let client = transaction.client();
+        // every valid transaction opens the account of its client
+        if account_storage.find(&client).is_none() {
+            store(account_storage.as_mut(), self.open(client))?;
+        }
+        self.expire_holds(account_storage.as_mut(), Some(client), now)
+            .await?;
 
-        if acc.locked() {
+        if let Err(e) = account_storage
+            .get(&client)
+            .state()
+            .check(transaction.transaction_type())
+        {
             #[cfg(debug_assertions)]
-            warn!("Transaction ignored for locked account: {:?}", transaction);
-            return Err(format!("Account is locked!"));
+            debug!("Transaction ignored, {}: {:?}", e, transaction);
+            return Err(e);
         }
 
         let transaction_storage = &mut self.storage.transactions.lock().await;
-        match transaction.transaction_type() {
-            TransactionType::Deposit => acc.deposit(&transaction.amount().unwrap()),
+        if !transaction.transaction_type().is_reference()
+            && transaction_storage.get(&transaction.tx()).is_some()
+        {
+            #[cfg(debug_assertions)]
+            debug!("Transaction ignored, duplicate: {:?}", transaction);
+            return Err(ProcessingError::DuplicateTransaction);
+        }
+        let outcome = match transaction.transaction_type() {
+            TransactionType::Deposit => {
+                let mut acc = account_storage.get(&client);
+                acc.deposit(&transaction.amount().unwrap())?;
+                store(account_storage.as_mut(), acc)?;
+                Outcome::Deposited
+            },
             TransactionType::Withdrawal => {
-                acc.withdrawal(&transaction.amount().unwrap())?
+                let mut acc = account_storage.get(&client);
+                acc.withdrawal(&transaction.amount().unwrap())?;
+                store(account_storage.as_mut(), acc)?;
+                Outcome::Withdrawn
             },
-            TransactionType::Dispute => {
-                let t = transaction_storage.get(&transaction.tx());
-                if let None = t {
-                    // Partner did a mistake and referred to a transaction not available.
-                    #[cfg(debug_assertions)]
-                    debug!("Transaction ignored, reference not existing: {:?}", transaction);
-                    return Ok(());
-                }
-                // check if client is the same as in the referred transaction
-                if transaction.client() != t.unwrap().client() {
+            TransactionType::Transfer => {
+                let amount = transaction.amount().unwrap();
+                let recipient = transaction.recipient().unwrap();
+                self.with_recipient(
+                    account_storage.as_mut(),
+                    client,
+                    recipient,
+                    TransactionType::Transfer,
+                    |from, to| {
+                        from.withdrawal(&amount)?;
+                        to.deposit(&amount)
+                    },
+                )
+                .await?;
+                Outcome::Transferred
+            },
+            TransactionType::Authorize => {
+                let amount = transaction.amount().unwrap();
+                let mut acc = account_storage.get(&client);
+                acc.authorize(&amount)?;
+                store(account_storage.as_mut(), acc)?;
+                let hold = Hold::new(client, amount);
+                let hold = match expires_at {
+                    Some(time) => hold.with_expiry(time),
+                    None => hold,
+                };
+                self.storage
+                    .hold_register
+                    .lock()
+                    .await
+                    .set_hold(&transaction.tx(), hold);
+                Outcome::Authorized
+            },
+            TransactionType::Capture
+            | TransactionType::Void
+            | TransactionType::Expire => {
+                let h = &mut self.storage.hold_register.lock().await;
+                let hold = match h.hold(&transaction.tx()) {
+                    Some(hold) => hold,
+                    None => {
+                        // Partner referred to a transaction that is no authorization.
+                        #[cfg(debug_assertions)]
+                        debug!("Transaction ignored, authorization not existing: {:?}", transaction);
+                        return Err(ProcessingError::UnknownReference);
+                    },
+                };
+                if client != hold.client() {
                     #[cfg(debug_assertions)]
                     debug!("Transaction ignored, client ids do not match: {:?}", transaction);
-                    return Ok(());
+                    return Err(ProcessingError::ClientMismatch);
                 }
-                // add to dispute register
-                let d = &mut self.storage.dispute_register.lock().await;
-                d.dispute(&t.unwrap().tx());
-
-                acc.dispute(&t.unwrap().amount().unwrap());
+                let (hold, amount) = match hold
+                    .next(transaction.transaction_type(), transaction.amount())
+                {
+                    Ok(v) => v,
+                    Err(e) => {
+                        // Partner did a mistake, eg. authorization has been voided.
+                        #[cfg(debug_assertions)]
+                        debug!("Transaction ignored, {}: {:?}", e, transaction);
+                        return Err(e);
+                    },
+                };
+                let mut acc = account_storage.get(&client);
+                let outcome = match transaction.transaction_type() {
+                    TransactionType::Capture => {
+                        acc.capture(&amount)?;
+                        Outcome::Captured
+                    },
+                    TransactionType::Void => {
+                        acc.release(&amount)?;
+                        Outcome::Voided
+                    },
+                    _ => {
+                        acc.release(&amount)?;
+                        Outcome::Expired
+                    },
+                };
+                store(account_storage.as_mut(), acc)?;
+                h.set_hold(&transaction.tx(), hold);
                 // transaction must not be saved because it is only referencing
                 // another one
-                return Ok(());
+                return Ok(outcome);
+            },
+            TransactionType::Freeze
+            | TransactionType::Unfreeze
+            | TransactionType::Unlock
+            | TransactionType::Close => {
+                let mut acc = account_storage.get(&client);
+                let disputes = self.storage.dispute_register.lock().await;
+                if *transaction.transaction_type() == TransactionType::Close
+                    && !is_settled(
+                        &acc,
+                        transaction_storage.as_ref(),
+                        disputes.as_ref(),
+                    )
+                {
+                    #[cfg(debug_assertions)]
+                    debug!("Transaction ignored, not settled: {:?}", transaction);
+                    return Err(ProcessingError::NotSettled);
+                }
+                // the transition has been checked above
+                let state = acc.next_state(transaction.transaction_type())?;
+                acc.change_state(state, transaction.reason().unwrap_or_default());
+                store(account_storage.as_mut(), acc)?;
+                match transaction.transaction_type() {
+                    TransactionType::Freeze => Outcome::Frozen,
+                    TransactionType::Unfreeze => Outcome::Unfrozen,
+                    TransactionType::Unlock => Outcome::Unlocked,
+                    _ => Outcome::Closed,
+                }
             },
-            TransactionType::Resolve | TransactionType::Chargeback => {
+            TransactionType::Dispute
+            | TransactionType::Resolve
+            | TransactionType::Chargeback => {
                 let t = transaction_storage.get(&transaction.tx());
-                if let None = t {
+                if t.is_none() {
                     // Partner did a mistake and referred to a transaction not available.
                     #[cfg(debug_assertions)]
                     debug!("Transaction ignored, reference not existing: {:?}", transaction);
-                    return Ok(());
+                    return Err(ProcessingError::UnknownReference);
                 }
+                let t = t.unwrap();
+                let transaction_type = *transaction.transaction_type();
                 // check if client is the same as in the referred transaction
-                if transaction.client() != t.unwrap().client() {
+                if client != t.client() {
                     #[cfg(debug_assertions)]
                     debug!("Transaction ignored, client ids do not match: {:?}", transaction);
-                    return Ok(());
+                    return Err(ProcessingError::ClientMismatch);
                 }
-                // check if in dispute register
+                // check if the referenced transaction can be disputed at all,
+                // eg. administrative transactions have no amount
+                let disputable = match transaction_type {
+                    TransactionType::Dispute => {
+                        dispute_policy.allows(t.transaction_type())
+                    },
+                    _ => t.transaction_type().is_disputable(),
+                };
+                let original = match (disputable, t.amount()) {
+                    (true, Some(amount)) => amount,
+                    _ => {
+                        #[cfg(debug_assertions)]
+                        debug!("Transaction ignored, not disputable: {:?}", transaction);
+                        return Err(ProcessingError::NotDisputable);
+                    },
+                };
+                // check if the dispute state allows the transaction
                 let d = &mut self.storage.dispute_register.lock().await;
-                if !d.is_dispute(&t.unwrap().tx()) {
-                    // Partner did a mistake, tx is not under dispute.
-                    #[cfg(debug_assertions)]
-                    debug!("Transaction ignored, tx is not under dispute: {:?}", transaction);
-                    return Ok(());
+                let next = d.dispute(&t.tx()).next(
+                    transaction.transaction_type(),
+                    transaction.amount(),
+                    original,
+                );
+                let (dispute, amount) = match next {
+                    Ok(v) => v,
+                    Err(e) => {
+                        // Partner did a mistake, eg. tx is not under dispute.
+                        #[cfg(debug_assertions)]
+                        debug!("Transaction ignored, {}: {:?}", e, transaction);
+                        return Err(e);
+                    },
+                };
+                let outcome = match transaction_type {
+                    TransactionType::Dispute => Outcome::Disputed,
+                    TransactionType::Chargeback => Outcome::ChargedBack,
+                    _ => Outcome::Resolved,
+                };
+                match t.recipient() {
+                    // the amount of a transfer has been credited to the
+                    // recipient, so the recipient is affected by the dispute
+                    Some(recipient) => {
+                        self.with_recipient(
+                            account_storage.as_mut(),
+                            client,
+                            recipient,
+                            transaction_type,
+                            |from, to| match transaction_type {
+                                TransactionType::Dispute => to.dispute(&amount),
+                                TransactionType::Chargeback => {
+                                    to.chargeback(&amount)?;
+                                    from.deposit(&amount)
+                                },
+                                _ => to.resolve(&amount),
+                            },
+                        )
+                        .await?
+                    },
+                    // the withdrawn amount is claimed back by the client
+                    None if *t.transaction_type() == TransactionType::Withdrawal => {
+                        let mut acc = account_storage.get(&client);
+                        match transaction_type {
+                            TransactionType::Dispute => acc.dispute_withdrawal(&amount)?,
+                            TransactionType::Chargeback => {
+                                acc.chargeback_withdrawal(&amount)?
+                            },
+                            _ => acc.resolve_withdrawal(&amount)?,
+                        }
+                        store(account_storage.as_mut(), acc)?
+                    },
+                    None => {
+                        let mut acc = account_storage.get(&client);
+                        match transaction_type {
+                            TransactionType::Dispute => acc.dispute(&amount)?,
+                            TransactionType::Chargeback => acc.chargeback(&amount)?,
+                            _ => acc.resolve(&amount)?,
+                        }
+                        store(account_storage.as_mut(), acc)?
+                    },
                 }
-                match transaction.transaction_type() {
-                    TransactionType::Resolve => acc.resolve(&t.unwrap().amount().unwrap()),
-                    TransactionType::Chargeback => acc.chargeback(&t.unwrap().amount().unwrap()),
-                    _ => (),
-                }
-                // remove from dispute register
-                d.resolve(&t.unwrap().tx());
+                d.set_dispute(&t.tx(), dispute);
                 // transaction must not be saved because it is only referencing
                 // another one
-                return Ok(());
+                return Ok(outcome);
             },
+        };
+
+        // store processed transaction, uniqueness has been checked above
+        let _ = transaction_storage.add(transaction);
+
+        Ok(outcome)
+    }
+
+    /// Releases the remaining amount of the open authorizations that are
+    /// expired at the given time, either of the given client or of all
+    /// clients of the storage.
+    async fn expire_holds(
+        &self,
+        accounts: &mut dyn AccountStorage,
+        client: Option<AccountId>,
+        now: SystemTime,
+    ) -> Result<(), ProcessingError> {
+        // authorizations hold their amount, so there is nothing to expire
+        // for a client without held amount
+        if let Some(client) = client {
+            if accounts.get(&client).held().is_zero() {
+                return Ok(());
+            }
+        }
+        let h = &mut self.storage.hold_register.lock().await;
+        let expired: Vec<_> = h
+            .holds()
+            .filter(|(_, hold)| {
+                hold.is_expired(now)
+                    && client.is_none_or(|c| c == hold.client())
+            })
+            .collect();
+        for (tx, hold) in expired {
+            let (hold, amount) = hold.next(&TransactionType::Expire, None)?;
+            let mut acc = accounts.get(&hold.client());
+            acc.release(&amount)?;
+            store(accounts, acc)?;
+            h.set_hold(&tx, hold);
+            #[cfg(debug_assertions)]
+            debug!("Authorization {} expired", tx);
+        }
+        Ok(())
+    }
+
+    /// Returns a new account of the client with its credit limit.
+    fn open(&self, client: AccountId) -> Account {
+        let mut account = Account::new(client);
+        if let Some(limits) = &self.credit_limits {
+            account.set_credit_limit(limits.get(&client));
         }
+        account
+    }
+
+    /// Returns the stored account of the client, or a new one if the client
+    /// has none yet.
+    fn account(
+        &self,
+        accounts: &dyn AccountStorage,
+        client: &AccountId,
+    ) -> Account {
+        accounts
+            .find(client)
+            .cloned()
+            .unwrap_or_else(|| self.open(*client))
+    }
 
-        // store processed transaction
-        transaction_storage.add(transaction);
+    /// Returns the storage of the shard the client belongs to.
+    fn storage_of(&self, client: &AccountId) -> &Arc<Storage> {
+        &self.shards[shard_index(client, self.shards.len())]
+    }
 
+    /// Applies the given function to the accounts of the client and the
+    /// recipient. The function works on copies, which are only stored if it
+    /// succeeded, so either both accounts are changed or none of them. The
+    /// account of the client is stored first and restored if the one of the
+    /// recipient can not be stored.
+    async fn with_recipient<F>(
+        &self,
+        accounts: &mut dyn AccountStorage,
+        client: AccountId,
+        recipient: AccountId,
+        transaction_type: TransactionType,
+        apply: F,
+    ) -> Result<(), ProcessingError>
+    where
+        F: FnOnce(&mut Account, &mut Account) -> Result<(), ProcessingError>,
+    {
+        let mut from = accounts.get(&client);
+        let previous = from.clone();
+        let storage = self.storage_of(&recipient);
+        // the dealer of the recipient waits until the transaction has been
+        // applied, so the storage is not in use
+        let mut others = if Arc::ptr_eq(storage, &self.storage) {
+            None
+        } else {
+            Some(storage.accounts.lock().await)
+        };
+        let mut to = match &others {
+            Some(others) => self.account(others.as_ref(), &recipient),
+            None => self.account(accounts, &recipient),
+        };
+        if !to.state().accepts_transfers(&transaction_type) {
+            return Err(ProcessingError::RecipientLocked);
+        }
+        apply(&mut from, &mut to)?;
+        store(accounts, from)?;
+        let stored = match &mut others {
+            Some(others) => store(others.as_mut(), to),
+            None => store(accounts, to),
+        };
+        if let Err(e) = stored {
+            if store(accounts, previous).is_err() {
+                error!(
+                    "Account {} not restored after a failed transfer to {}",
+                    client, recipient
+                );
+            }
+            return Err(e);
+        }
         Ok(())
     }
 }
+
+/// Writes the changed account back to the storage.
+fn store(
+    accounts: &mut dyn AccountStorage,
+    account: Account,
+) -> Result<(), ProcessingError> {
+    accounts.set(account).map_err(|_| ProcessingError::NotStored)
+}
+
+/// Returns true if the client of the account has neither open authorizations
+/// nor open disputes.
+fn is_settled(
+    account: &Account,
+    transactions: &dyn TransactionStorage,
+    disputes: &dyn DisputeRegister,
+) -> bool {
+    // open authorizations and disputes hold their amount
+    if !account.held().is_zero() {
+        return false;
+    }
+    // except disputes of transfers, which hold it on the account of the
+    // recipient
+    !disputes.disputes().any(|(tx, dispute)| {
+        dispute.state() == DisputeState::Disputed
+            && transactions
+                .get(&tx)
+                .is_some_and(|t| t.client() == account.id())
+    })
+}
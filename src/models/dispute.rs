@@ -0,0 +1,205 @@
+use {
+    crate::models::{CreditAmount, ProcessingError, TransactionType},
+    std::{fmt, str::FromStr},
+};
+
+/// Defines which transactions can be disputed and how a disputed withdrawal
+/// changes the account.
+///
+/// A disputed deposit holds its amount until the dispute is resolved or the
+/// deposit is charged back. Withdrawals work the other way round, the client
+/// claims to get the withdrawn amount back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisputePolicy {
+    /// Deposits, withdrawals and transfers can be disputed. The amount of a
+    /// disputed withdrawal is credited as held until the dispute ends. A
+    /// resolve confirms the withdrawal and removes the held amount again, a
+    /// chargeback reverses the withdrawal and makes the amount available.
+    #[default]
+    ReverseWithdrawals,
+    /// Only deposits can be disputed, all other disputes are rejected.
+    DepositsOnly,
+}
+
+impl DisputePolicy {
+    /// Returns true if transactions of the given type can be disputed.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::{DisputePolicy, TransactionType};
+    /// let policy = DisputePolicy::DepositsOnly;
+    /// assert!(policy.allows(&TransactionType::Deposit));
+    /// assert!(!policy.allows(&TransactionType::Withdrawal));
+    /// ```
+    pub fn allows(self, transaction_type: &TransactionType) -> bool {
+        match self {
+            Self::ReverseWithdrawals => transaction_type.is_disputable(),
+            Self::DepositsOnly => *transaction_type == TransactionType::Deposit,
+        }
+    }
+}
+
+impl FromStr for DisputePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reverse-withdrawals" => Ok(Self::ReverseWithdrawals),
+            "deposits-only" => Ok(Self::DepositsOnly),
+            _ => Err(format!("Unknown dispute policy: {}", s)),
+        }
+    }
+}
+
+/// Dispute state of a stored transaction, derived from its [Dispute].
+///
+/// A transaction is `Disputed` as long as a part of it is outstanding. Once
+/// nothing is outstanding, it is `ChargedBack` if any part has been charged
+/// back, `Resolved` otherwise. Neither is final, the part of the amount that
+/// has not been disputed yet can still be disputed, but no part is disputed
+/// or reversed more than once.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub enum DisputeState {
+    /// The transaction has never been disputed.
+    #[default]
+    Normal,
+    /// The transaction is under dispute, its amount is held.
+    Disputed,
+    /// The dispute has been resolved.
+    Resolved,
+    /// The transaction has been reversed.
+    ChargedBack,
+}
+
+impl fmt::Display for DisputeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = match self {
+            Self::Normal => "normal",
+            Self::Disputed => "disputed",
+            Self::Resolved => "resolved",
+            Self::ChargedBack => "charged back",
+        };
+        write!(f, "{}", v)
+    }
+}
+
+/// Disputed amounts of a stored transaction.
+///
+/// A transaction can be disputed in parts, as long as the sum of all
+/// disputes does not exceed its amount. Every resolve or chargeback settles
+/// a part of the outstanding amount, the [DisputeState] is derived from the
+/// amounts.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub struct Dispute {
+    disputed: CreditAmount,
+    outstanding: CreditAmount,
+    charged_back: CreditAmount,
+}
+
+impl Dispute {
+    /// Returns the sum of all disputes.
+    pub fn disputed(&self) -> CreditAmount {
+        self.disputed
+    }
+
+    /// Returns the amount that is currently under dispute.
+    pub fn outstanding(&self) -> CreditAmount {
+        self.outstanding
+    }
+
+    /// Returns the sum of all chargebacks.
+    pub fn charged_back(&self) -> CreditAmount {
+        self.charged_back
+    }
+
+    /// Returns the state of the dispute. A transaction is under dispute as
+    /// long as an amount is outstanding.
+    pub fn state(&self) -> DisputeState {
+        if !self.outstanding.is_zero() {
+            DisputeState::Disputed
+        } else if !self.charged_back.is_zero() {
+            DisputeState::ChargedBack
+        } else if !self.disputed.is_zero() {
+            DisputeState::Resolved
+        } else {
+            DisputeState::Normal
+        }
+    }
+
+    /// Applies the given transaction type to the dispute of a transaction
+    /// with the given original amount. Without an amount, a dispute covers
+    /// the rest of the original amount and a resolve or chargeback the whole
+    /// outstanding amount. Returns the new dispute and the amount it has
+    /// been changed by.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::*;
+    /// let original = "10".parse().unwrap();
+    /// let part = "4".parse().ok();
+    /// let (d, amount) = Dispute::default()
+    ///     .next(&TransactionType::Dispute, part, original)
+    ///     .unwrap();
+    /// assert_eq!((d.state(), Some(amount)), (DisputeState::Disputed, part));
+    /// let (d, amount) = d.next(&TransactionType::Resolve, None, original).unwrap();
+    /// assert_eq!((d.state(), Some(amount)), (DisputeState::Resolved, part));
+    /// let (d, amount) = d.next(&TransactionType::Dispute, None, original).unwrap();
+    /// assert_eq!(amount, "6".parse().unwrap());
+    /// assert_eq!(
+    ///     d.next(&TransactionType::Chargeback, "7".parse().ok(), original),
+    ///     Err(ProcessingError::ExcessiveAmount)
+    /// );
+    /// ```
+    pub fn next(
+        self,
+        transaction_type: &TransactionType,
+        amount: Option<CreditAmount>,
+        original: CreditAmount,
+    ) -> Result<(Self, CreditAmount), ProcessingError> {
+        let limit = match transaction_type {
+            TransactionType::Dispute => original
+                .checked_sub(self.disputed)
+                .ok_or(ProcessingError::Overflow)?,
+            TransactionType::Resolve | TransactionType::Chargeback => {
+                self.outstanding
+            }
+            t => {
+                return Err(ProcessingError::IllegalDisputeTransition {
+                    from: self.state(),
+                    transaction_type: *t,
+                })
+            }
+        };
+        if limit.is_zero() || limit.is_negative() {
+            return Err(ProcessingError::IllegalDisputeTransition {
+                from: self.state(),
+                transaction_type: *transaction_type,
+            });
+        }
+        let amount = amount.unwrap_or(limit);
+        if amount > limit {
+            return Err(ProcessingError::ExcessiveAmount);
+        }
+        let add = |a: CreditAmount| {
+            a.checked_add(amount).ok_or(ProcessingError::Overflow)
+        };
+        let sub = |a: CreditAmount| {
+            a.checked_sub(amount).ok_or(ProcessingError::Overflow)
+        };
+        let mut next = self;
+        match transaction_type {
+            TransactionType::Dispute => {
+                next.disputed = add(self.disputed)?;
+                next.outstanding = add(self.outstanding)?;
+            }
+            TransactionType::Chargeback => {
+                next.outstanding = sub(self.outstanding)?;
+                next.charged_back = add(self.charged_back)?;
+            }
+            _ => next.outstanding = sub(self.outstanding)?,
+        }
+        Ok((next, amount))
+    }
+}
@@ -0,0 +1,235 @@
+use {
+    crate::models::{
+        AccountChange,
+        AccountId,
+        CreditAmount,
+        Hook,
+        Outcome,
+        ProcessingError,
+        Transaction,
+        TransactionId,
+    },
+    tokio::sync::broadcast,
+};
+
+/// Count of events a subscriber can fall behind before it misses events.
+pub const DEFAULT_EVENT_CAPACITY: usize = 1024;
+
+/// Change of an account that is published while the transactions are
+/// processed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AccountEvent {
+    /// The balance of the account changed by the transaction `tx`.
+    BalanceChanged {
+        /// The account.
+        client: AccountId,
+        /// The transaction that changed the balance.
+        tx: TransactionId,
+        /// Available amount after the transaction.
+        available: CreditAmount,
+        /// Held amount after the transaction.
+        held: CreditAmount,
+        /// Total amount after the transaction.
+        total: CreditAmount,
+    },
+    /// The transaction `tx` has been disputed.
+    DisputeOpened {
+        /// The account.
+        client: AccountId,
+        /// The disputed transaction.
+        tx: TransactionId,
+    },
+    /// The dispute of the transaction `tx` has been resolved.
+    DisputeResolved {
+        /// The account.
+        client: AccountId,
+        /// The disputed transaction.
+        tx: TransactionId,
+    },
+    /// The transaction `tx` has been charged back.
+    ChargedBack {
+        /// The account.
+        client: AccountId,
+        /// The disputed transaction.
+        tx: TransactionId,
+    },
+    /// The account has been locked.
+    AccountLocked {
+        /// The account.
+        client: AccountId,
+    },
+}
+
+impl AccountEvent {
+    /// Returns the events of an accepted transaction, given the accounts it
+    /// concerns before and after it as passed to [Hook::after]. A balance
+    /// change is published for every account whose amounts changed, eg.
+    /// disputes and resolves of transfers only change the account of the
+    /// recipient. A lock is only published for an account that has not been
+    /// locked before the transaction.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::*;
+    /// let mut account = Account::new(1);
+    /// account.deposit(&"5".parse().unwrap()).unwrap();
+    /// let change = AccountChange::new(Account::new(1), account);
+    /// let t = Transaction::new(TransactionType::Deposit, 1, 7, "5".parse().ok());
+    /// assert_eq!(
+    ///     AccountEvent::of(&t, &Outcome::Deposited, &[change]),
+    ///     vec![AccountEvent::BalanceChanged {
+    ///         client: 1,
+    ///         tx: 7,
+    ///         available: "5".parse().unwrap(),
+    ///         held: CreditAmount::ZERO,
+    ///         total: "5".parse().unwrap(),
+    ///     }]
+    /// );
+    /// ```
+    pub fn of(
+        transaction: &Transaction,
+        outcome: &Outcome,
+        changes: &[AccountChange],
+    ) -> Vec<Self> {
+        let client = transaction.client();
+        let tx = transaction.tx();
+        let mut events: Vec<Self> = changes
+            .iter()
+            .filter(|c| {
+                let (before, after) = (c.before(), c.after());
+                before.available() != after.available()
+                    || before.held() != after.held()
+                    || before.total() != after.total()
+            })
+            .map(|c| Self::BalanceChanged {
+                client: c.after().id(),
+                tx,
+                available: c.after().available(),
+                held: c.after().held(),
+                total: c.after().total(),
+            })
+            .collect();
+        match outcome {
+            Outcome::Disputed => events.push(Self::DisputeOpened { client, tx }),
+            Outcome::Resolved => {
+                events.push(Self::DisputeResolved { client, tx })
+            }
+            Outcome::ChargedBack => {
+                events.push(Self::ChargedBack { client, tx });
+                // the chargeback locks the account the amount is taken from,
+                // unless an earlier chargeback already did
+                for c in changes
+                    .iter()
+                    .filter(|c| !c.before().locked() && c.after().locked())
+                {
+                    events.push(Self::AccountLocked {
+                        client: c.after().id(),
+                    });
+                }
+            }
+            _ => (),
+        }
+        events
+    }
+}
+
+/// [Hook] that publishes the [AccountEvent]s of every accepted transaction
+/// to its subscribers. Subscribers that fall behind by more than the
+/// capacity miss the oldest events.
+/// # Example
+/// ```rust
+/// use athanasia::{models::*, Engine};
+/// let amount = |v: &str| v.parse().unwrap();
+/// let balance = |client, tx, available, held, total| AccountEvent::BalanceChanged {
+///     client,
+///     tx,
+///     available: amount(available),
+///     held: amount(held),
+///     total: amount(total),
+/// };
+/// // client 1 and 2 are processed by different dealers, client 3 by the
+/// // dealer of client 1
+/// let mut engine = Engine::new(2);
+/// let mut events = engine.subscribe();
+/// let transactions = futures::stream::iter(vec![
+///     Transaction::new(TransactionType::Deposit, 1, 1, "10".parse().ok()),
+///     Transaction::new(TransactionType::Transfer, 1, 2, "4".parse().ok())
+///         .with_recipient(2),
+///     Transaction::new(TransactionType::Dispute, 1, 2, None),
+///     Transaction::new(TransactionType::Chargeback, 1, 2, None),
+///     Transaction::new(TransactionType::Deposit, 3, 5, "1".parse().ok()),
+///     Transaction::new(TransactionType::Deposit, 3, 6, "2".parse().ok()),
+///     Transaction::new(TransactionType::Dispute, 3, 5, None),
+///     Transaction::new(TransactionType::Dispute, 3, 6, None),
+///     Transaction::new(TransactionType::Chargeback, 3, 5, None),
+///     Transaction::new(TransactionType::Chargeback, 3, 6, None),
+/// ]);
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// rt.block_on(engine.process_stream(transactions)).unwrap();
+/// let received: Vec<_> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+/// assert_eq!(
+///     received[1..],
+///     [
+///         balance(1, 2, "6", "0", "6"),
+///         balance(2, 2, "4", "0", "4"),
+///         balance(2, 2, "0", "4", "4"),
+///         AccountEvent::DisputeOpened { client: 1, tx: 2 },
+///         balance(1, 2, "10", "0", "10"),
+///         balance(2, 2, "0", "0", "0"),
+///         AccountEvent::ChargedBack { client: 1, tx: 2 },
+///         AccountEvent::AccountLocked { client: 2 },
+///         balance(3, 5, "1", "0", "1"),
+///         balance(3, 6, "3", "0", "3"),
+///         balance(3, 5, "2", "1", "3"),
+///         AccountEvent::DisputeOpened { client: 3, tx: 5 },
+///         balance(3, 6, "0", "3", "3"),
+///         AccountEvent::DisputeOpened { client: 3, tx: 6 },
+///         balance(3, 5, "0", "2", "2"),
+///         AccountEvent::ChargedBack { client: 3, tx: 5 },
+///         AccountEvent::AccountLocked { client: 3 },
+///         // the account is already locked by the first chargeback
+///         balance(3, 6, "0", "0", "0"),
+///         AccountEvent::ChargedBack { client: 3, tx: 6 },
+///     ]
+/// );
+/// ```
+#[derive(Debug)]
+pub struct EventBroadcast {
+    sender: broadcast::Sender<AccountEvent>,
+}
+
+impl Default for EventBroadcast {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENT_CAPACITY)
+    }
+}
+
+impl EventBroadcast {
+    /// Creates a broadcast that keeps up to `capacity` events for every
+    /// subscriber.
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self { sender }
+    }
+
+    /// Returns a receiver of all events that are published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<AccountEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Hook for EventBroadcast {
+    fn after(
+        &self,
+        transaction: &Transaction,
+        result: &Result<Outcome, ProcessingError>,
+        changes: &[AccountChange],
+    ) {
+        if let Ok(outcome) = result {
+            for event in AccountEvent::of(transaction, outcome, changes) {
+                // sending only fails if nobody is subscribed
+                let _ = self.sender.send(event);
+            }
+        }
+    }
+}
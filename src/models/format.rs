@@ -0,0 +1,132 @@
+use {
+    serde::Serialize,
+    std::{
+        io::{self, Write},
+        path::Path,
+        str::FromStr,
+    },
+};
+
+/// Data format of the input and the outputs. JSON is only supported as
+/// output, input in JSON has to be given as JSON Lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Comma separated values including a header.
+    #[default]
+    Csv,
+    /// A single JSON array containing all records.
+    Json,
+    /// One JSON object per line, also known as NDJSON.
+    JsonLines,
+}
+
+impl Format {
+    /// Determines the format by the extension of the given file. Returns
+    /// [None] if the extension is unknown.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::Format;
+    /// assert_eq!(Format::from_path("tx.ndjson"), Some(Format::JsonLines));
+    /// assert_eq!(Format::from_path("accounts.JSON"), Some(Format::Json));
+    /// assert_eq!(Format::from_path("tx"), None);
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?;
+        extension.to_ascii_lowercase().parse().ok()
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "ndjson" | "jsonl" => Ok(Self::JsonLines),
+            _ => Err(format!("Unknown format: {}", s)),
+        }
+    }
+}
+
+enum Inner<'a> {
+    Csv(Box<csv::Writer<Box<dyn Write + Send + 'a>>>),
+    Json {
+        writer: Box<dyn Write + Send + 'a>,
+        started: bool,
+    },
+    JsonLines(Box<dyn Write + Send + 'a>),
+}
+
+/// Writes records one by one in the given [Format]. Records are serialized
+/// the same way in every format, so the fields match the csv columns.
+pub struct RecordWriter<'a> {
+    inner: Inner<'a>,
+}
+
+impl<'a> RecordWriter<'a> {
+    /// Creates a new writer.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::*;
+    /// let mut out = vec![];
+    /// let mut writer = RecordWriter::new(Format::Json, &mut out);
+    /// writer.write(&Account::new(3)).unwrap();
+    /// writer.finish().unwrap();
+    /// drop(writer);
+    /// assert_eq!(
+    ///     String::from_utf8(out).unwrap(),
+    ///     "[\n{\"id\":3,\"available\":\"0.0000\",\"held\":\"0.0000\",\
+    ///      \"total\":\"0.0000\",\"locked\":false,\"credit_limit\":\"0.0000\"}\n]\n"
+    /// );
+    /// ```
+    pub fn new<W: Write + Send + 'a>(format: Format, writer: W) -> Self {
+        let writer: Box<dyn Write + Send + 'a> = Box::new(writer);
+        let inner = match format {
+            Format::Csv => {
+                Inner::Csv(Box::new(csv::Writer::from_writer(writer)))
+            }
+            Format::Json => Inner::Json {
+                writer,
+                started: false,
+            },
+            Format::JsonLines => Inner::JsonLines(writer),
+        };
+        Self { inner }
+    }
+
+    /// Writes a single record.
+    pub fn write<T: Serialize>(&mut self, record: &T) -> io::Result<()> {
+        match &mut self.inner {
+            Inner::Csv(w) => w.serialize(record)?,
+            Inner::Json { writer, started } => {
+                writer.write_all(if *started { b",\n" } else { b"[\n" })?;
+                *started = true;
+                serde_json::to_writer(&mut *writer, record)?;
+            }
+            Inner::JsonLines(w) => {
+                serde_json::to_writer(&mut *w, record)?;
+                w.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes all buffered records.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            Inner::Csv(w) => w.flush(),
+            Inner::Json { writer, .. } => writer.flush(),
+            Inner::JsonLines(w) => w.flush(),
+        }
+    }
+
+    /// Completes the output, eg. closes the JSON array, and flushes it. No
+    /// records must be written afterwards.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Inner::Json { writer, started } = &mut self.inner {
+            writer.write_all(if *started { b"\n]\n" } else { b"[]\n" })?;
+        }
+        self.flush()
+    }
+}
@@ -0,0 +1,191 @@
+use {
+    crate::models::{
+        rules::seconds,
+        AccountId,
+        CreditAmount,
+        ProcessingError,
+        TransactionType,
+    },
+    std::{
+        fmt,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// Lifecycle of an authorization hold.
+///
+/// A hold is `Open` until its whole amount has been captured, or until the
+/// rest of it has been released by a void or an expiry. All other states are
+/// final. A hold expires by an [TransactionType::Expire] row, or on its own
+/// once its expiry time has passed, see [Hold::with_expiry].
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub enum HoldState {
+    /// Funds are reserved and can be captured.
+    #[default]
+    Open,
+    /// The whole amount has been captured.
+    Captured,
+    /// The rest of the amount has been released by a void.
+    Voided,
+    /// The rest of the amount has been released because the authorization
+    /// expired.
+    Expired,
+}
+
+impl fmt::Display for HoldState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = match self {
+            Self::Open => "open",
+            Self::Captured => "captured",
+            Self::Voided => "voided",
+            Self::Expired => "expired",
+        };
+        write!(f, "{}", v)
+    }
+}
+
+/// Funds reserved by an authorization. They are part of the held amount of
+/// the account, but tracked apart from disputes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Hold {
+    client: AccountId,
+    remaining: CreditAmount,
+    captured: CreditAmount,
+    state: HoldState,
+    /// Seconds since the unix epoch, missing in snapshots written before
+    /// holds could expire.
+    #[serde(default)]
+    expires_at: Option<u64>,
+}
+
+impl Hold {
+    /// Creates an open hold of the given amount.
+    pub fn new(client: AccountId, amount: CreditAmount) -> Self {
+        Self {
+            client,
+            remaining: amount,
+            captured: CreditAmount::ZERO,
+            state: HoldState::Open,
+            expires_at: None,
+        }
+    }
+
+    /// Makes the hold expire at the given time. Without an expiry time the
+    /// hold only expires by an [TransactionType::Expire] row.
+    /// # Example
+    /// ```rust
+    /// use {
+    ///     athanasia::models::*,
+    ///     std::time::{Duration, UNIX_EPOCH},
+    /// };
+    /// let time = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    /// let hold = Hold::new(1, "10".parse().unwrap())
+    ///     .with_expiry(time + Duration::from_secs(60));
+    /// assert!(!hold.is_expired(time));
+    /// assert!(hold.is_expired(time + Duration::from_secs(60)));
+    /// let (hold, _) = hold.next(&TransactionType::Void, None).unwrap();
+    /// assert!(!hold.is_expired(time + Duration::from_secs(60)));
+    /// ```
+    pub fn with_expiry(mut self, time: SystemTime) -> Self {
+        self.expires_at = Some(seconds(time));
+        self
+    }
+
+    /// Returns the client whose funds are held.
+    pub fn client(&self) -> AccountId {
+        self.client
+    }
+
+    /// Returns the amount that is still reserved.
+    pub fn remaining(&self) -> CreditAmount {
+        self.remaining
+    }
+
+    /// Returns the sum of all captures.
+    pub fn captured(&self) -> CreditAmount {
+        self.captured
+    }
+
+    /// Returns the state of the hold.
+    pub fn state(&self) -> HoldState {
+        self.state
+    }
+
+    /// Returns the time the hold expires at, if any.
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.expires_at
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    /// Returns true if the hold is still open and its expiry time has passed
+    /// at the given time.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.state == HoldState::Open
+            && self.expires_at.is_some_and(|at| at <= seconds(now))
+    }
+
+    /// Applies the given transaction type to the hold. A capture takes the
+    /// given part of the remaining amount, or all of it without an amount.
+    /// A void or expiry releases the remaining amount. Returns the new hold
+    /// and the amount it has been changed by.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::*;
+    /// let hold = Hold::new(1, "10".parse().unwrap());
+    /// let (hold, amount) = hold
+    ///     .next(&TransactionType::Capture, "4".parse().ok())
+    ///     .unwrap();
+    /// assert_eq!((hold.state(), amount), (HoldState::Open, "4".parse().unwrap()));
+    /// let (hold, amount) = hold.next(&TransactionType::Void, None).unwrap();
+    /// assert_eq!((hold.state(), amount), (HoldState::Voided, "6".parse().unwrap()));
+    /// assert_eq!(
+    ///     hold.next(&TransactionType::Capture, None),
+    ///     Err(ProcessingError::IllegalHoldTransition {
+    ///         from: HoldState::Voided,
+    ///         transaction_type: TransactionType::Capture,
+    ///     })
+    /// );
+    /// ```
+    pub fn next(
+        self,
+        transaction_type: &TransactionType,
+        amount: Option<CreditAmount>,
+    ) -> Result<(Self, CreditAmount), ProcessingError> {
+        let state = match transaction_type {
+            TransactionType::Capture => HoldState::Captured,
+            TransactionType::Void => HoldState::Voided,
+            TransactionType::Expire => HoldState::Expired,
+            _ => self.state,
+        };
+        if self.state != HoldState::Open || state == self.state {
+            return Err(ProcessingError::IllegalHoldTransition {
+                from: self.state,
+                transaction_type: *transaction_type,
+            });
+        }
+        let mut next = self;
+        if state != HoldState::Captured {
+            next.remaining = CreditAmount::ZERO;
+            next.state = state;
+            return Ok((next, self.remaining));
+        }
+        let amount = amount.unwrap_or(self.remaining);
+        if amount > self.remaining {
+            return Err(ProcessingError::ExcessiveAmount);
+        }
+        next.remaining = self
+            .remaining
+            .checked_sub(amount)
+            .ok_or(ProcessingError::Overflow)?;
+        next.captured = self
+            .captured
+            .checked_add(amount)
+            .ok_or(ProcessingError::Overflow)?;
+        if next.remaining.is_zero() {
+            next.state = HoldState::Captured;
+        }
+        Ok((next, amount))
+    }
+}
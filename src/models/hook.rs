@@ -0,0 +1,85 @@
+use crate::models::{Account, Outcome, ProcessingError, Transaction};
+
+/// Custom logic that runs around every transaction a [Dealer] processes,
+/// eg. fraud scoring or notifications. Hooks are registered on the
+/// [Engine](crate::Engine) and called by the dealer of the client, so the
+/// transactions of one client reach a hook in order.
+///
+/// Both methods do nothing by default. Transactions that are replayed from
+/// the write-ahead log are not passed to the hooks again.
+///
+/// [Dealer]: crate::models::Dealer
+/// # Example
+/// ```rust
+/// use {
+///     athanasia::{models::*, Engine},
+///     std::sync::Arc,
+/// };
+/// struct Limit;
+///
+/// impl Hook for Limit {
+///     fn before(&self, transaction: &Transaction) -> Result<(), String> {
+///         match transaction.amount() {
+///             Some(a) if a > "100".parse().unwrap() => Err("too large".to_string()),
+///             _ => Ok(()),
+///         }
+///     }
+/// }
+///
+/// let engine = Engine::new(1).with_hook(Arc::new(Limit));
+/// let rows = vec![
+///     Transaction::new(TransactionType::Deposit, 1, 1, "500".parse().ok()),
+///     Transaction::new(TransactionType::Deposit, 1, 2, "5".parse().ok()),
+/// ];
+/// let rt = tokio::runtime::Runtime::new().unwrap();
+/// let accounts = rt
+///     .block_on(engine.process_stream(futures::stream::iter(rows)))
+///     .unwrap();
+/// assert_eq!(accounts[0].total(), "5".parse().unwrap());
+/// ```
+pub trait Hook: Send + Sync {
+    /// Called before the transaction is processed. Returning an error
+    /// vetoes the transaction, it is rejected with
+    /// [ProcessingError::Vetoed] and the given reason.
+    fn before(&self, _transaction: &Transaction) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called after the transaction has been processed or rejected, with the
+    /// accounts it concerns before and after it. The account of the client
+    /// comes first, transfers and disputes of transfers are followed by the
+    /// account of the recipient. Unknown clients get an empty account that
+    /// is not stored.
+    fn after(
+        &self,
+        _transaction: &Transaction,
+        _result: &Result<Outcome, ProcessingError>,
+        _changes: &[AccountChange],
+    ) {
+    }
+}
+
+/// An account before and after a transaction, passed to [Hook::after].
+#[derive(Debug, Clone)]
+pub struct AccountChange {
+    before: Account,
+    after: Account,
+}
+
+impl AccountChange {
+    /// Creates the change of an account from its state before and after the
+    /// transaction.
+    pub fn new(before: Account, after: Account) -> Self {
+        Self { before, after }
+    }
+
+    /// Returns the account before the transaction.
+    pub fn before(&self) -> &Account {
+        &self.before
+    }
+
+    /// Returns the account after the transaction.
+    pub fn after(&self) -> &Account {
+        &self.after
+    }
+}
@@ -0,0 +1,194 @@
+use {
+    crate::models::{
+        Account,
+        AccountId,
+        AccountStorage,
+        Dispute,
+        DisputeRegister,
+        DisputeState,
+        Hold,
+        HoldRegister,
+        Transaction,
+        TransactionId,
+        TransactionStorage,
+    },
+    std::collections::HashMap,
+};
+
+/// Keeps all accounts in memory.
+#[derive(Serialize)]
+pub struct InMemoryAccountStorage(HashMap<AccountId, Account>);
+
+impl Default for InMemoryAccountStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryAccountStorage {
+    /// Creates a new account storage instance.
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+impl AccountStorage for InMemoryAccountStorage {
+    /// Updates the storage by the given account, or creates a new one if not
+    /// found.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::{
+    ///     Account,
+    ///     AccountStorage,
+    ///     CreditAmount,
+    ///     InMemoryAccountStorage,
+    /// };
+    /// let mut storage = InMemoryAccountStorage::new();
+    /// let a = Account::new(1);
+    /// storage.set(a);
+    /// assert_eq!(storage.get(&1).available(), CreditAmount::ZERO);
+    /// ```
+    fn set(&mut self, account: Account) -> Result<(), Account> {
+        let acc = self
+            .0
+            .entry(account.id())
+            .or_insert(Account::new(account.id()));
+        *acc = account;
+        Ok(())
+    }
+
+    fn find(&self, id: &AccountId) -> Option<&Account> {
+        self.0.get(id)
+    }
+
+    fn accounts(&self) -> Box<dyn Iterator<Item = &Account> + '_> {
+        Box::new(self.0.values())
+    }
+}
+
+/// Keeps all transactions in memory.
+pub struct InMemoryTransactionStorage(HashMap<TransactionId, Transaction>);
+
+impl Default for InMemoryTransactionStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryTransactionStorage {
+    /// Creates a new transaction storage instance.
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+impl TransactionStorage for InMemoryTransactionStorage {
+    fn add(&mut self, transaction: Transaction) -> Result<(), Transaction> {
+        if self.0.contains_key(&transaction.tx()) {
+            return Err(transaction);
+        }
+        self.0.insert(transaction.tx(), transaction);
+        Ok(())
+    }
+
+    fn get(&self, id: &TransactionId) -> Option<&Transaction> {
+        self.0.get(id)
+    }
+
+    fn transactions(&self) -> Box<dyn Iterator<Item = &Transaction> + '_> {
+        Box::new(self.0.values())
+    }
+}
+
+/// Keeps the disputes in memory.
+pub struct InMemoryDisputeRegister(HashMap<TransactionId, Dispute>);
+
+impl Default for InMemoryDisputeRegister {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryDisputeRegister {
+    /// Creates a new dispute register instance.
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+impl DisputeRegister for InMemoryDisputeRegister {
+    fn dispute(&self, id: &TransactionId) -> Dispute {
+        self.0.get(id).copied().unwrap_or_default()
+    }
+
+    /// Sets the dispute of the transaction.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::{
+    ///     Dispute,
+    ///     DisputeRegister,
+    ///     DisputeState,
+    ///     InMemoryDisputeRegister,
+    ///     TransactionType,
+    /// };
+    /// let mut d = InMemoryDisputeRegister::new();
+    /// let id = 300;
+    /// let id2 = 700;
+    /// let amount = "1".parse().unwrap();
+    /// let (disputed, _) = Dispute::default()
+    ///     .next(&TransactionType::Dispute, None, amount)
+    ///     .unwrap();
+    /// d.set_dispute(&id, disputed);
+    /// d.set_dispute(&id2, disputed);
+    /// assert_eq!(d.is_dispute(&id), true);
+    /// let (resolved, _) = disputed
+    ///     .next(&TransactionType::Resolve, None, amount)
+    ///     .unwrap();
+    /// d.set_dispute(&id, resolved);
+    /// assert_eq!(d.is_dispute(&id), false);
+    /// assert_eq!(d.state(&id), DisputeState::Resolved);
+    /// assert_eq!(d.is_dispute(&id2), true);
+    /// ```
+    fn set_dispute(&mut self, id: &TransactionId, dispute: Dispute) {
+        self.0.insert(*id, dispute);
+    }
+
+    fn disputes(&self) -> Box<dyn Iterator<Item = (TransactionId, Dispute)> + '_> {
+        Box::new(
+            self.0
+                .iter()
+                .filter(|(_, d)| d.state() != DisputeState::Normal)
+                .map(|(id, d)| (*id, *d)),
+        )
+    }
+}
+
+/// Keeps the authorization holds in memory.
+pub struct InMemoryHoldRegister(HashMap<TransactionId, Hold>);
+
+impl Default for InMemoryHoldRegister {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryHoldRegister {
+    /// Creates a new hold register instance.
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+impl HoldRegister for InMemoryHoldRegister {
+    fn hold(&self, id: &TransactionId) -> Option<Hold> {
+        self.0.get(id).copied()
+    }
+
+    fn set_hold(&mut self, id: &TransactionId, hold: Hold) {
+        self.0.insert(*id, hold);
+    }
+
+    fn holds(&self) -> Box<dyn Iterator<Item = (TransactionId, Hold)> + '_> {
+        Box::new(self.0.iter().map(|(id, h)| (*id, *h)))
+    }
+}
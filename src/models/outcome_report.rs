@@ -0,0 +1,170 @@
+use {
+    crate::models::{
+        AccountId,
+        Format,
+        Outcome,
+        ProcessingError,
+        RecordWriter,
+        RejectedRow,
+        Transaction,
+        TransactionId,
+        TransactionType,
+    },
+    std::{
+        fs::File,
+        io::{self, BufWriter, Write},
+        path::Path,
+        sync::Mutex,
+    },
+};
+
+/// Value of the outcome column for rejected transactions.
+const REJECTED: &str = "rejected";
+
+/// Value of the outcome column for duplicates that have been dropped.
+const IGNORED: &str = "ignored";
+
+/// Outcome column of the report.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+enum ReportedOutcome {
+    Accepted(Outcome),
+    Rejected(&'static str),
+    Ignored(&'static str),
+}
+
+/// A single line of the report. The fields of the transaction are empty if
+/// the row could not be read.
+#[derive(Serialize, Debug)]
+struct OutcomeRecord {
+    #[serde(rename(serialize = "type"))]
+    transaction_type: Option<TransactionType>,
+    client: Option<AccountId>,
+    tx: Option<TransactionId>,
+    amount: Option<String>,
+    to: Option<AccountId>,
+    outcome: ReportedOutcome,
+    reason: Option<String>,
+}
+
+/// Lists every input transaction together with its outcome, or the reason
+/// why it has been rejected. Accepted state changes of an account are listed
+/// with their reason. Used to reconcile with partners without reading the
+/// log files.
+///
+/// Lines are written as soon as a transaction has been processed, so they
+/// are ordered by processing time.
+pub struct OutcomeReport {
+    writer: Mutex<RecordWriter<'static>>,
+}
+
+impl OutcomeReport {
+    /// Creates a report that writes to the given writer in the given format.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::*;
+    /// let path = std::env::temp_dir().join("athanasia-report-doctest.csv");
+    /// let report = OutcomeReport::create(&path, Format::Csv).unwrap();
+    /// let t = Transaction::new(TransactionType::Withdrawal, 1, 2, "1".parse().ok());
+    /// report.record(&t, &Err(ProcessingError::InsufficientFunds)).unwrap();
+    /// report.finish().unwrap();
+    /// assert_eq!(
+    ///     std::fs::read_to_string(&path).unwrap(),
+    ///     "type,client,tx,amount,to,outcome,reason\n\
+    ///      withdrawal,1,2,1.0000,,rejected,insufficient funds\n"
+    /// );
+    /// ```
+    pub fn new<W: Write + Send + 'static>(writer: W, format: Format) -> Self {
+        Self {
+            writer: Mutex::new(RecordWriter::new(format, writer)),
+        }
+    }
+
+    /// Creates a report that writes to the given file in the given format.
+    pub fn create<P: AsRef<Path>>(path: P, format: Format) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?), format))
+    }
+
+    /// Adds the result of the given transaction to the report.
+    pub fn record(
+        &self,
+        transaction: &Transaction,
+        result: &Result<Outcome, ProcessingError>,
+    ) -> io::Result<()> {
+        let (outcome, reason) = match result {
+            Ok(o) => (
+                ReportedOutcome::Accepted(*o),
+                transaction.reason().map(str::to_string),
+            ),
+            Err(e) => (ReportedOutcome::Rejected(REJECTED), Some(e.to_string())),
+        };
+        self.write(OutcomeRecord {
+            transaction_type: Some(*transaction.transaction_type()),
+            client: Some(transaction.client()),
+            tx: Some(transaction.tx()),
+            amount: transaction.amount().map(|a| a.to_string()),
+            to: transaction.recipient(),
+            outcome,
+            reason,
+        })
+    }
+
+    /// Adds a duplicate to the report that has been dropped without being
+    /// processed, see [DuplicatePolicy::Ignore](crate::models::DuplicatePolicy::Ignore).
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::*;
+    /// let path = std::env::temp_dir().join("athanasia-report-ignored-doctest.csv");
+    /// let report = OutcomeReport::create(&path, Format::Csv).unwrap();
+    /// let t = Transaction::new(TransactionType::Deposit, 1, 2, "1".parse().ok());
+    /// report.record_ignored(&t).unwrap();
+    /// report.finish().unwrap();
+    /// assert_eq!(
+    ///     std::fs::read_to_string(&path).unwrap(),
+    ///     "type,client,tx,amount,to,outcome,reason\n\
+    ///      deposit,1,2,1.0000,,ignored,duplicate transaction id\n"
+    /// );
+    /// ```
+    pub fn record_ignored(&self, transaction: &Transaction) -> io::Result<()> {
+        self.write(OutcomeRecord {
+            transaction_type: Some(*transaction.transaction_type()),
+            client: Some(transaction.client()),
+            tx: Some(transaction.tx()),
+            amount: transaction.amount().map(|a| a.to_string()),
+            to: transaction.recipient(),
+            outcome: ReportedOutcome::Ignored(IGNORED),
+            reason: Some(ProcessingError::DuplicateTransaction.to_string()),
+        })
+    }
+
+    /// Adds an input row to the report that does not form a valid
+    /// transaction. The fields are reported as they have been read, or left
+    /// empty if the row could not be read at all.
+    pub fn record_invalid(&self, row: &RejectedRow) -> io::Result<()> {
+        let record = row.record();
+        self.write(OutcomeRecord {
+            transaction_type: record.map(|r| *r.transaction_type()),
+            client: record.map(|r| r.client()),
+            tx: record.map(|r| r.tx()),
+            amount: record.and_then(|r| r.amount()).map(str::to_string),
+            to: record.and_then(|r| r.recipient()),
+            outcome: ReportedOutcome::Rejected(REJECTED),
+            reason: Some(row.error().to_string()),
+        })
+    }
+
+    /// Writes all buffered lines.
+    pub fn flush(&self) -> io::Result<()> {
+        self.writer.lock().unwrap().flush()
+    }
+
+    /// Completes and flushes the report, nothing must be recorded
+    /// afterwards.
+    pub fn finish(&self) -> io::Result<()> {
+        self.writer.lock().unwrap().finish()
+    }
+
+    fn write(&self, record: OutcomeRecord) -> io::Result<()> {
+        self.writer.lock().unwrap().write(&record)
+    }
+}
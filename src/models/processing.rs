@@ -0,0 +1,199 @@
+use {
+    crate::models::{
+        AccountState,
+        DisputeState,
+        HoldState,
+        TransactionType,
+        ValidationError,
+    },
+    std::fmt,
+};
+
+/// Describes how an accepted transaction changed the account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// Credit has been added to the account.
+    Deposited,
+    /// Credit has been withdrawn from the account.
+    Withdrawn,
+    /// The referenced transaction is now under dispute, its amount is held.
+    Disputed,
+    /// The dispute has been resolved, the held amount is available again.
+    Resolved,
+    /// The disputed transaction has been reversed and the account locked.
+    ChargedBack,
+    /// Credit has been moved to the account of the recipient.
+    Transferred,
+    /// Credit has been reserved by an authorization hold.
+    Authorized,
+    /// Reserved credit has been taken from the account.
+    Captured,
+    /// The rest of the reserved credit is available again.
+    Voided,
+    /// The authorization expired, the rest of the reserved credit is
+    /// available again.
+    Expired,
+    /// The account has been frozen.
+    Frozen,
+    /// The frozen account is active again.
+    Unfrozen,
+    /// The locked account is active again.
+    Unlocked,
+    /// The account has been closed.
+    Closed,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = match self {
+            Self::Deposited => "deposited",
+            Self::Withdrawn => "withdrawn",
+            Self::Disputed => "disputed",
+            Self::Resolved => "resolved",
+            Self::ChargedBack => "charged back",
+            Self::Transferred => "transferred",
+            Self::Authorized => "authorized",
+            Self::Captured => "captured",
+            Self::Voided => "voided",
+            Self::Expired => "expired",
+            Self::Frozen => "frozen",
+            Self::Unfrozen => "unfrozen",
+            Self::Unlocked => "unlocked",
+            Self::Closed => "closed",
+        };
+        write!(f, "{}", v)
+    }
+}
+
+/// Reason why a transaction has been rejected. A rejected transaction does
+/// not change any account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessingError {
+    /// The transaction does not meet the requirements of its type.
+    Invalid(ValidationError),
+    /// A transaction with the same tx has already been processed.
+    DuplicateTransaction,
+    /// The account is locked and does not accept any transactions.
+    AccountLocked,
+    /// The account of the recipient of a transfer is locked or closed.
+    RecipientLocked,
+    /// The state of the account does not accept the transaction type, eg. a
+    /// withdrawal from a frozen account.
+    NotAccepted {
+        /// State of the account.
+        state: AccountState,
+        /// Type of the rejected transaction.
+        transaction_type: TransactionType,
+    },
+    /// The available amount is lower than the requested withdrawal.
+    InsufficientFunds,
+    /// The referenced transaction does not exist.
+    UnknownReference,
+    /// The client does not match the client of the referenced transaction.
+    ClientMismatch,
+    /// The referenced transaction can not be disputed by the configured
+    /// [DisputePolicy](crate::models::DisputePolicy).
+    NotDisputable,
+    /// The amount exceeds the amount of the referenced transaction that can
+    /// be disputed, that is under dispute for a resolve or chargeback, or
+    /// that is reserved by an authorization for a capture.
+    ExcessiveAmount,
+    /// The referenced transaction is not in a dispute state that allows the
+    /// transaction type, eg. a resolve on a transaction that is not under
+    /// dispute or a dispute on a transaction that has been charged back.
+    IllegalDisputeTransition {
+        /// Dispute state of the referenced transaction.
+        from: DisputeState,
+        /// Type of the rejected transaction.
+        transaction_type: TransactionType,
+    },
+    /// The referenced authorization is not in a state that allows the
+    /// transaction type, eg. a capture of a voided authorization.
+    IllegalHoldTransition {
+        /// State of the referenced authorization.
+        from: HoldState,
+        /// Type of the rejected transaction.
+        transaction_type: TransactionType,
+    },
+    /// A rule of the [RuleSet](crate::models::RuleSet) rejected the
+    /// transaction, contains the name of the rule.
+    RuleViolation(String),
+    /// A [Hook](crate::models::Hook) vetoed the transaction, contains the
+    /// reason of the hook.
+    Vetoed(String),
+    /// The account can not be closed while authorizations or disputes of
+    /// the client are open.
+    NotSettled,
+    /// The [AccountStorage](crate::models::AccountStorage) failed to store
+    /// the changed account.
+    NotStored,
+    /// The resulting amount does not fit into the value range.
+    Overflow,
+}
+
+impl fmt::Display for ProcessingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = match self {
+            Self::Invalid(e) => return write!(f, "invalid transaction: {}", e),
+            Self::DuplicateTransaction => "duplicate transaction id",
+            Self::AccountLocked => "account is locked",
+            Self::RecipientLocked => {
+                "account of the recipient does not accept transfers"
+            }
+            Self::NotAccepted {
+                state,
+                transaction_type,
+            } => {
+                return write!(
+                    f,
+                    "{:?} not accepted by {} account",
+                    transaction_type, state
+                )
+            }
+            Self::InsufficientFunds => "insufficient funds",
+            Self::UnknownReference => "referenced transaction not existing",
+            Self::ClientMismatch => "client ids do not match",
+            Self::NotDisputable => "transaction type can not be disputed",
+            Self::ExcessiveAmount => {
+                "amount exceeds the remaining amount of the referenced transaction"
+            }
+            Self::IllegalDisputeTransition {
+                from,
+                transaction_type,
+            } => {
+                return write!(
+                    f,
+                    "{:?} not allowed on {} transaction",
+                    transaction_type, from
+                )
+            }
+            Self::IllegalHoldTransition {
+                from,
+                transaction_type,
+            } => {
+                return write!(
+                    f,
+                    "{:?} not allowed on {} authorization",
+                    transaction_type, from
+                )
+            }
+            Self::RuleViolation(name) => {
+                return write!(f, "rejected by rule {}", name)
+            }
+            Self::Vetoed(reason) => return write!(f, "vetoed: {}", reason),
+            Self::NotSettled => "open authorizations or disputes",
+            Self::NotStored => "account not stored",
+            Self::Overflow => "amount overflow",
+        };
+        write!(f, "{}", v)
+    }
+}
+
+impl std::error::Error for ProcessingError {}
+
+impl From<ValidationError> for ProcessingError {
+    fn from(e: ValidationError) -> Self {
+        Self::Invalid(e)
+    }
+}
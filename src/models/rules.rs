@@ -0,0 +1,262 @@
+use {
+    crate::models::{
+        AccountId,
+        CreditAmount,
+        ProcessingError,
+        Transaction,
+        TransactionType,
+    },
+    std::{
+        collections::{HashMap, HashSet, VecDeque},
+        fs::File,
+        io::{self, BufReader, Read},
+        path::Path,
+        sync::Mutex,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// Seconds of a day, daily totals are reset at midnight UTC.
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Condition of a [Rule] that rejects a transaction.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleKind {
+    /// Rejects transactions with an amount greater than the given one.
+    MaxAmount {
+        /// Largest accepted amount.
+        amount: CreditAmount,
+    },
+    /// Rejects transactions that raise the sum of the amounts of the
+    /// client on the current day above the given one. Applies to
+    /// withdrawals if no transaction types are given.
+    DailyTotal {
+        /// Largest accepted sum per day.
+        amount: CreditAmount,
+    },
+    /// Rejects transactions of a client that already had the given count of
+    /// transactions within the window.
+    Velocity {
+        /// Count of accepted transactions per window.
+        count: usize,
+        /// Length of the window in seconds.
+        window_secs: u64,
+    },
+    /// Rejects all transactions of the given clients.
+    BlockedClients {
+        /// The blocked clients.
+        clients: HashSet<AccountId>,
+    },
+}
+
+/// A named rule, the name is reported when the rule rejects a transaction.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    name: String,
+    /// Types the rule applies to, all types if not given.
+    #[serde(default)]
+    transaction_types: Option<Vec<TransactionType>>,
+    #[serde(flatten)]
+    kind: RuleKind,
+}
+
+impl Rule {
+    /// Returns the name of the rule.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the condition of the rule.
+    pub fn kind(&self) -> &RuleKind {
+        &self.kind
+    }
+
+    /// Returns true if the rule applies to the transaction type.
+    /// Administrative transactions are never checked.
+    fn applies(&self, transaction_type: &TransactionType) -> bool {
+        if transaction_type.is_admin() {
+            return false;
+        }
+        match (&self.transaction_types, &self.kind) {
+            (Some(types), _) => types.contains(transaction_type),
+            (None, RuleKind::DailyTotal { .. }) => {
+                *transaction_type == TransactionType::Withdrawal
+            }
+            (None, _) => true,
+        }
+    }
+
+    fn violation(&self) -> ProcessingError {
+        ProcessingError::RuleViolation(self.name.clone())
+    }
+}
+
+/// Activity of a client that is tracked for a single rule.
+#[derive(Debug, Default)]
+struct Activity {
+    day: u64,
+    total: CreditAmount,
+    times: VecDeque<u64>,
+}
+
+/// The rules file, a JSON object with a list of rules.
+#[derive(Deserialize, Debug)]
+struct RulesFile {
+    rules: Vec<Rule>,
+}
+
+/// Declarative rules that are checked before a transaction is applied, eg.
+/// maximum amounts or velocity checks. Keeps track of the accepted
+/// transactions that are required by the rules.
+///
+/// The rules are read from a JSON file:
+/// ```json
+/// {"rules": [
+///     {"name": "max-deposit", "type": "max_amount", "amount": "10000",
+///      "transaction_types": ["deposit"]},
+///     {"name": "daily-withdrawals", "type": "daily_total", "amount": "5000"},
+///     {"name": "velocity", "type": "velocity", "count": 10, "window_secs": 60},
+///     {"name": "blocked", "type": "blocked_clients", "clients": [7, 9]}
+/// ]}
+/// ```
+#[derive(Debug, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    activity: Mutex<HashMap<(usize, AccountId), Activity>>,
+}
+
+impl RuleSet {
+    /// Reads the rules from the given JSON file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads the rules from the given JSON input.
+    pub fn from_reader<R: Read>(reader: R) -> io::Result<Self> {
+        let file: RulesFile =
+            serde_json::from_reader(reader).map_err(io::Error::from)?;
+        Ok(Self::new(file.rules))
+    }
+
+    /// Creates a rule set of the given rules.
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self {
+            rules,
+            activity: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the rules.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Checks the transaction against all rules at the given time. Returns a
+    /// [ProcessingError::RuleViolation] with the name of the first rule that
+    /// rejects it.
+    /// # Example
+    /// ```rust
+    /// use {athanasia::models::*, std::time::SystemTime};
+    /// let input: &[u8] = br#"{"rules": [
+    ///     {"name": "max-deposit", "type": "max_amount", "amount": "100",
+    ///      "transaction_types": ["deposit"]},
+    ///     {"name": "daily-withdrawals", "type": "daily_total", "amount": "50"}
+    /// ]}"#;
+    /// let rules = RuleSet::from_reader(input).unwrap();
+    /// let now = SystemTime::now();
+    /// let t = Transaction::new(TransactionType::Deposit, 1, 1, "150".parse().ok());
+    /// assert_eq!(
+    ///     rules.check(&t, now),
+    ///     Err(ProcessingError::RuleViolation("max-deposit".to_string()))
+    /// );
+    /// let t = Transaction::new(TransactionType::Withdrawal, 1, 2, "30".parse().ok());
+    /// assert_eq!(rules.check(&t, now), Ok(()));
+    /// rules.record(&t, now);
+    /// let t = Transaction::new(TransactionType::Withdrawal, 1, 3, "30".parse().ok());
+    /// assert_eq!(
+    ///     rules.check(&t, now),
+    ///     Err(ProcessingError::RuleViolation("daily-withdrawals".to_string()))
+    /// );
+    /// ```
+    pub fn check(
+        &self,
+        transaction: &Transaction,
+        now: SystemTime,
+    ) -> Result<(), ProcessingError> {
+        let now = seconds(now);
+        let client = transaction.client();
+        let activity = self.activity.lock().unwrap();
+        for (i, rule) in self.rules.iter().enumerate() {
+            if !rule.applies(transaction.transaction_type()) {
+                continue;
+            }
+            let a = activity.get(&(i, client));
+            let rejected = match &rule.kind {
+                RuleKind::MaxAmount { amount } => {
+                    transaction.amount().is_some_and(|v| v > *amount)
+                }
+                RuleKind::DailyTotal { amount } => {
+                    let total = a
+                        .filter(|a| a.day == now / SECONDS_PER_DAY)
+                        .map_or(CreditAmount::ZERO, |a| a.total);
+                    let value = transaction.amount().unwrap_or_default();
+                    total.checked_add(value).is_none_or(|v| v > *amount)
+                }
+                RuleKind::Velocity { count, window_secs } => {
+                    let recent = a.map_or(0, |a| {
+                        a.times
+                            .iter()
+                            .filter(|t| now < **t + *window_secs)
+                            .count()
+                    });
+                    recent >= *count
+                }
+                RuleKind::BlockedClients { clients } => clients.contains(&client),
+            };
+            if rejected {
+                return Err(rule.violation());
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds an accepted transaction to the activity of its client.
+    pub fn record(&self, transaction: &Transaction, now: SystemTime) {
+        let now = seconds(now);
+        let client = transaction.client();
+        let mut activity = self.activity.lock().unwrap();
+        for (i, rule) in self.rules.iter().enumerate() {
+            if !rule.applies(transaction.transaction_type()) {
+                continue;
+            }
+            match &rule.kind {
+                RuleKind::DailyTotal { .. } => {
+                    let a = activity.entry((i, client)).or_default();
+                    let day = now / SECONDS_PER_DAY;
+                    if a.day != day {
+                        a.day = day;
+                        a.total = CreditAmount::ZERO;
+                    }
+                    let value = transaction.amount().unwrap_or_default();
+                    a.total = a.total.checked_add(value).unwrap_or(a.total);
+                }
+                RuleKind::Velocity { count, window_secs } => {
+                    let a = activity.entry((i, client)).or_default();
+                    while a.times.front().is_some_and(|t| t + window_secs <= now)
+                        || a.times.len() >= (*count).max(1)
+                    {
+                        a.times.pop_front();
+                    }
+                    a.times.push_back(now);
+                }
+                RuleKind::MaxAmount { .. } | RuleKind::BlockedClients { .. } => (),
+            }
+        }
+    }
+}
+
+/// Returns the seconds since the unix epoch.
+pub(crate) fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
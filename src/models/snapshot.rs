@@ -0,0 +1,227 @@
+use {
+    crate::models::{
+        Account,
+        AccountId,
+        Dispute,
+        Hold,
+        StateChange,
+        Storage,
+        Transaction,
+        TransactionId,
+    },
+    std::{
+        collections::HashMap,
+        fs::{self, File},
+        io::{self, BufReader, BufWriter, Write},
+        path::Path,
+        sync::Arc,
+    },
+};
+
+/// Version of the snapshot format that is written by this crate.
+pub const SNAPSHOT_VERSION: u32 = 6;
+
+/// Dispute of a single transaction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct DisputeEntry {
+    tx: TransactionId,
+    dispute: Dispute,
+}
+
+/// Authorization hold of a single transaction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct HoldEntry {
+    tx: TransactionId,
+    hold: Hold,
+}
+
+/// State change of a single account.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct StateChangeEntry {
+    client: AccountId,
+    change: StateChange,
+}
+
+/// Full state of all storages at a point in time: the accounts, the stored
+/// transactions, their disputes, the authorization holds and the state
+/// changes of the accounts.
+///
+/// A snapshot is written as a versioned JSON file. Starting the engine from a
+/// snapshot applies the new input on top of the saved state, eg. processing
+/// todays file on top of yesterdays closing balances.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Snapshot {
+    version: u32,
+    accounts: Vec<Account>,
+    transactions: Vec<Transaction>,
+    disputes: Vec<DisputeEntry>,
+    holds: Vec<HoldEntry>,
+    state_changes: Vec<StateChangeEntry>,
+}
+
+impl Snapshot {
+    /// Captures the state of the given storages. The entries are sorted, so
+    /// the same state always results in the same snapshot.
+    pub async fn capture(storages: &[Arc<Storage>]) -> Self {
+        let mut accounts = vec![];
+        let mut transactions = vec![];
+        let mut disputes = vec![];
+        let mut holds = vec![];
+        let mut state_changes = vec![];
+        for storage in storages {
+            let a = storage.accounts.lock().await;
+            accounts.extend(a.accounts().cloned());
+            for account in a.accounts() {
+                state_changes.extend(account.history().iter().map(|change| {
+                    StateChangeEntry {
+                        client: account.id(),
+                        change: change.clone(),
+                    }
+                }));
+            }
+            let t = storage.transactions.lock().await;
+            transactions.extend(t.transactions().cloned());
+            let d = storage.dispute_register.lock().await;
+            disputes.extend(d.disputes().map(|(tx, dispute)| DisputeEntry {
+                tx,
+                dispute,
+            }));
+            let h = storage.hold_register.lock().await;
+            holds.extend(h.holds().map(|(tx, hold)| HoldEntry { tx, hold }));
+        }
+        accounts.sort_by_key(|a| a.id());
+        transactions.sort_by_key(|t| t.tx());
+        disputes.sort_by_key(|d| d.tx);
+        holds.sort_by_key(|h| h.tx);
+        // stable, so the changes of an account stay in order
+        state_changes.sort_by_key(|c| c.client);
+        Self {
+            version: SNAPSHOT_VERSION,
+            accounts,
+            transactions,
+            disputes,
+            holds,
+            state_changes,
+        }
+    }
+
+    /// Writes the state into the given storages. `shard` returns the index of
+    /// the storage that is responsible for a client.
+    pub async fn restore<F>(
+        &self,
+        storages: &[Arc<Storage>],
+        shard: F,
+    ) -> io::Result<()>
+    where
+        F: Fn(&AccountId) -> usize,
+    {
+        let mut clients = HashMap::new();
+        for t in &self.transactions {
+            clients.insert(t.tx(), t.client());
+            let storage = &storages[shard(&t.client())];
+            if storage.transactions.lock().await.add(t.clone()).is_err() {
+                return Err(invalid_data(format!(
+                    "Snapshot contains transaction {} twice",
+                    t.tx()
+                )));
+            }
+        }
+        let mut history: HashMap<AccountId, Vec<StateChange>> = HashMap::new();
+        for c in &self.state_changes {
+            history.entry(c.client).or_default().push(c.change.clone());
+        }
+        for a in &self.accounts {
+            let storage = &storages[shard(&a.id())];
+            let mut a = a.clone();
+            for change in history.remove(&a.id()).unwrap_or_default() {
+                a.restore_history(change);
+            }
+            if let Err(a) = storage.accounts.lock().await.set(a) {
+                return Err(invalid_data(format!(
+                    "Account {} could not be restored",
+                    a.id()
+                )));
+            }
+        }
+        for d in &self.disputes {
+            let client = clients.get(&d.tx).ok_or_else(|| {
+                invalid_data(format!(
+                    "Snapshot contains dispute of unknown transaction {}",
+                    d.tx
+                ))
+            })?;
+            let storage = &storages[shard(client)];
+            storage.dispute_register.lock().await.set_dispute(&d.tx, d.dispute);
+        }
+        for h in &self.holds {
+            let storage = &storages[shard(&h.hold.client())];
+            storage.hold_register.lock().await.set_hold(&h.tx, h.hold);
+        }
+        Ok(())
+    }
+
+    /// Returns the accounts of the snapshot.
+    pub fn accounts(&self) -> &[Account] {
+        &self.accounts
+    }
+
+    /// Returns the stored transactions of the snapshot.
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// Reads a snapshot from the given file. Fails if the file has been
+    /// written in a different version.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let snapshot: Self =
+            serde_json::from_reader(reader).map_err(io::Error::from)?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(invalid_data(format!(
+                "Snapshot version {} is not supported, expected {}",
+                snapshot.version, SNAPSHOT_VERSION
+            )));
+        }
+        Ok(snapshot)
+    }
+
+    /// Writes the snapshot to the given file. The file is replaced at once,
+    /// so an existing snapshot is never left half written.
+    /// # Example
+    /// ```rust
+    /// use {athanasia::models::*, std::sync::Arc};
+    /// let path = std::env::temp_dir().join("athanasia-snapshot-doctest.json");
+    /// let storages = vec![Arc::new(Storage::new())];
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///     let mut account = Account::new(7);
+    ///     account.deposit(&"3".parse().unwrap()).unwrap();
+    ///     storages[0].accounts.lock().await.set(account).unwrap();
+    ///     Snapshot::capture(&storages).await.save(&path).unwrap();
+    ///
+    ///     let restored = vec![Arc::new(Storage::new())];
+    ///     let snapshot = Snapshot::load(&path).unwrap();
+    ///     snapshot.restore(&restored, |_| 0).await.unwrap();
+    ///     let total = restored[0].accounts.lock().await.get(&7).total();
+    ///     assert_eq!(total, "3".parse().unwrap());
+    /// });
+    /// ```
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        {
+            let file = File::create(&tmp)?;
+            let mut writer = BufWriter::new(&file);
+            serde_json::to_writer(&mut writer, self).map_err(io::Error::from)?;
+            writer.flush()?;
+            drop(writer);
+            file.sync_all()?;
+        }
+        fs::rename(&tmp, path)
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -1,124 +1,163 @@
 use {
-    crate::models::{Account, AccountId, Transaction, TransactionId},
-    std::collections::HashMap,
+    crate::models::{
+        Account,
+        AccountId,
+        Dispute,
+        DisputeState,
+        Hold,
+        InMemoryAccountStorage,
+        InMemoryDisputeRegister,
+        InMemoryHoldRegister,
+        InMemoryTransactionStorage,
+        Transaction,
+        TransactionId,
+    },
     tokio::sync::Mutex,
 };
 
-/// Combines accounts as well as all transactions and dispute transaction ids.
+/// Combines accounts as well as all transactions, dispute transaction ids and
+/// authorization holds.
+/// Every part is a trait object, so the backends can be replaced by own
+/// implementations, eg. for persistence or testing.
 pub struct Storage {
     /// Stores accounts by client id.
-    pub accounts: Mutex<AccountStorage>,
+    pub accounts: Mutex<Box<dyn AccountStorage>>,
     /// Stores transactions by tx.
-    pub transactions: Mutex<TransactionStorage>,
-    /// Stores transaction ids that are under dispute.
-    pub dispute_register: Mutex<DisputeRegister>,
+    pub transactions: Mutex<Box<dyn TransactionStorage>>,
+    /// Stores the dispute state of transactions.
+    pub dispute_register: Mutex<Box<dyn DisputeRegister>>,
+    /// Stores the authorization holds.
+    pub hold_register: Mutex<Box<dyn HoldRegister>>,
 }
 
-impl Storage {
-    /// Creates a new storage.
-    pub fn new() -> Self {
-        Self {
-            accounts: Mutex::new(AccountStorage::new()),
-            transactions: Mutex::new(TransactionStorage::new()),
-            dispute_register: Mutex::new(DisputeRegister::new()),
-        }
+impl Default for Storage {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-/// Contains all accounts.
-#[derive(Serialize)]
-pub struct AccountStorage(HashMap<AccountId, Account>);
-
-impl AccountStorage {
-    /// Creates a new account storage instance.
+impl Storage {
+    /// Creates a new storage using the in-memory backends.
     pub fn new() -> Self {
-        Self(HashMap::new())
+        Self::with_backends(
+            Box::new(InMemoryAccountStorage::new()),
+            Box::new(InMemoryTransactionStorage::new()),
+            Box::new(InMemoryDisputeRegister::new()),
+        )
     }
 
-    /// Updates the storage by the given account, or creates a new one if not
-    /// found.
+    /// Creates a new storage using the given backends.
     /// # Example
     /// ```rust
-    /// use athanasia::{models::AccountStorage, models::Account};
-    /// let mut storage = AccountStorage::new();
-    /// let a = Account::new(1);
-    /// storage.set(a);
-    /// assert_eq!(storage.get(&1).available(), 0.0);
+    /// use athanasia::{models::*, Engine};
+    /// let engine = Engine::new(2).with_storage(|_shard| {
+    ///     Storage::with_backends(
+    ///         Box::new(InMemoryAccountStorage::new()),
+    ///         Box::new(InMemoryTransactionStorage::new()),
+    ///         Box::new(InMemoryDisputeRegister::new()),
+    ///     )
+    /// });
     /// ```
-    pub fn set(&mut self, account: Account) -> Result<(), Account> {
-        let acc = self
-            .0
-            .entry(account.id())
-            .or_insert(Account::new(account.id()));
-        *acc = account;
-        Ok(())
-    }
-
-    /// Returns the stored account for the given id.
-    pub fn get(&mut self, id: &AccountId) -> &mut Account {
-        self.0.entry(*id).or_insert(Account::new(*id))
+    pub fn with_backends(
+        accounts: Box<dyn AccountStorage>,
+        transactions: Box<dyn TransactionStorage>,
+        dispute_register: Box<dyn DisputeRegister>,
+    ) -> Self {
+        Self {
+            accounts: Mutex::new(accounts),
+            transactions: Mutex::new(transactions),
+            dispute_register: Mutex::new(dispute_register),
+            hold_register: Mutex::new(Box::new(InMemoryHoldRegister::new())),
+        }
     }
 
-    pub fn get_map(&self) -> &HashMap<AccountId, Account> {
-        &self.0
+    /// Replaces the backend of the authorization holds, which defaults to
+    /// the in-memory one.
+    /// # Example
+    /// ```rust
+    /// use athanasia::models::*;
+    /// let storage = Storage::new()
+    ///     .with_hold_register(Box::new(InMemoryHoldRegister::new()));
+    /// ```
+    pub fn with_hold_register(
+        mut self,
+        hold_register: Box<dyn HoldRegister>,
+    ) -> Self {
+        self.hold_register = Mutex::new(hold_register);
+        self
     }
 }
 
-/// Storage for all transactions.
-pub struct TransactionStorage(HashMap<TransactionId, Transaction>);
+/// Contains all accounts.
+/// Accounts are never changed in place, every change is written back using
+/// [AccountStorage::set], so a backend is able to persist it.
+pub trait AccountStorage: Send {
+    /// Updates the storage by the given account, or creates a new one if not
+    /// found. Returns the account as error if it could not be stored.
+    fn set(&mut self, account: Account) -> Result<(), Account>;
 
-impl TransactionStorage {
-    /// Creates a new transaction storage instance.
-    pub fn new() -> Self {
-        Self(HashMap::new())
+    /// Returns the stored account for the given id if available.
+    fn find(&self, id: &AccountId) -> Option<&Account> {
+        self.accounts().find(|a| a.id() == *id)
     }
 
-    /// Adds the given transaction to the storage.
-    /// If transaction id is already present, the transaction is being ignored.
-    pub fn add(&mut self, transaction: Transaction) {
-        self.0.insert(transaction.tx(), transaction);
+    /// Returns a copy of the stored account for the given id, or a new
+    /// account if not found. The new account is not stored.
+    fn get(&self, id: &AccountId) -> Account {
+        self.find(id).cloned().unwrap_or_else(|| Account::new(*id))
     }
 
+    /// Returns all stored accounts in no particular order.
+    fn accounts(&self) -> Box<dyn Iterator<Item = &Account> + '_>;
+}
+
+/// Storage for all transactions.
+pub trait TransactionStorage: Send {
+    /// Adds the given transaction to the storage.
+    /// If transaction id is already present, the transaction is being ignored
+    /// and returned as error.
+    fn add(&mut self, transaction: Transaction) -> Result<(), Transaction>;
+
     /// Gets a transaction from the storage if available.
-    pub fn get(&self, id: &TransactionId) -> Option<&Transaction> {
-        self.0.get(id)
-    }
+    fn get(&self, id: &TransactionId) -> Option<&Transaction>;
+
+    /// Returns all stored transactions in no particular order.
+    fn transactions(&self) -> Box<dyn Iterator<Item = &Transaction> + '_>;
 }
 
-/// Contains ids of transactions currently under dispute.
-pub struct DisputeRegister(Vec<TransactionId>);
+/// Contains the dispute of every transaction that has been disputed.
+/// Transactions that are not in the register have never been disputed and
+/// are in the [DisputeState::Normal] state.
+pub trait DisputeRegister: Send {
+    /// Returns the dispute of the transaction.
+    fn dispute(&self, id: &TransactionId) -> Dispute;
 
-impl DisputeRegister {
-    /// Creates a new dispute register instance.
-    pub fn new() -> Self {
-        Self(vec![])
-    }
+    /// Sets the dispute of the transaction.
+    fn set_dispute(&mut self, id: &TransactionId, dispute: Dispute);
 
-    /// Adds the transaction id to the dispute list.
-    pub fn dispute(&mut self, id: &TransactionId) {
-        self.0.push(*id);
-    }
+    /// Returns all transactions that are not in the [DisputeState::Normal]
+    /// state, in no particular order.
+    fn disputes(&self) -> Box<dyn Iterator<Item = (TransactionId, Dispute)> + '_>;
 
-    /// Removes the transaction id from the dispute list.
-    /// # Example
-    /// ```rust
-    /// use athanasia::models::{DisputeRegister, TransactionId};
-    /// let mut d = DisputeRegister::new();
-    /// let id = 300;
-    /// let id2 = 700;
-    /// d.dispute(&id);
-    /// d.dispute(&id2);
-    /// assert_eq!(d.is_dispute(&id), true);
-    /// d.resolve(&id);
-    /// assert_eq!(d.is_dispute(&id), false);
-    /// assert_eq!(d.is_dispute(&id2), true);
-    /// ```
-    pub fn resolve(&mut self, id: &TransactionId) {
-        self.0.retain(|&x| x != *id);
+    /// Returns the dispute state of the transaction.
+    fn state(&self, id: &TransactionId) -> DisputeState {
+        self.dispute(id).state()
     }
 
-    /// Checks if the transaction id is on the dispute list.
-    pub fn is_dispute(&mut self, id: &TransactionId) -> bool {
-        self.0.contains(id)
+    /// Checks if the transaction is currently under dispute.
+    fn is_dispute(&self, id: &TransactionId) -> bool {
+        self.state(id) == DisputeState::Disputed
     }
 }
+
+/// Contains the authorization holds by the tx of the authorization.
+pub trait HoldRegister: Send {
+    /// Returns the hold of the authorization if available.
+    fn hold(&self, id: &TransactionId) -> Option<Hold>;
+
+    /// Sets the hold of the authorization.
+    fn set_hold(&mut self, id: &TransactionId, hold: Hold);
+
+    /// Returns all holds in no particular order.
+    fn holds(&self) -> Box<dyn Iterator<Item = (TransactionId, Hold)> + '_>;
+}
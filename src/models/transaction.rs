@@ -1,17 +1,10 @@
-use {
-    crate::models::AccountId,
-    serde::{Deserialize, Serializer},
-};
+use crate::models::{AccountId, CreditAmount};
 
 /// Defines a transaction id.
 pub type TransactionId = u32;
 
-/// Abstraction for a list of transactions.
-/// Vec used intentionally because queues crate requires clone crate.
-pub type TransactionQueue = Vec<Transaction>;
-
 /// Defines the type of a transaction.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TransactionType {
     /// Adds credit to the clients account.
     #[serde(rename(serialize = "deposit", deserialize = "deposit"))]
@@ -28,18 +21,85 @@ pub enum TransactionType {
     /// Final state of dispuse, representing reversing a transaction.
     #[serde(rename(serialize = "chargeback", deserialize = "chargeback"))]
     Chargeback,
+    /// Moves credit from the clients account to the account of the
+    /// recipient.
+    #[serde(rename(serialize = "transfer", deserialize = "transfer"))]
+    Transfer,
+    /// Reserves credit of the clients account by moving it from the
+    /// available to the held amount.
+    #[serde(rename(serialize = "authorize", deserialize = "authorize"))]
+    Authorize,
+    /// Takes reserved credit of an authorization from the account.
+    #[serde(rename(serialize = "capture", deserialize = "capture"))]
+    Capture,
+    /// Releases the rest of the reserved credit of an authorization.
+    #[serde(rename(serialize = "void", deserialize = "void"))]
+    Void,
+    /// Releases the rest of the reserved credit of an authorization that has
+    /// not been captured in time. Authorizations with an expiry time are
+    /// also released without this row once their time has passed.
+    #[serde(rename(serialize = "expire", deserialize = "expire"))]
+    Expire,
+    /// Administrative freeze of the clients account, no more credit can be
+    /// taken from it.
+    #[serde(rename(serialize = "freeze", deserialize = "freeze"))]
+    Freeze,
+    /// Administrative reactivation of a frozen account.
+    #[serde(rename(serialize = "unfreeze", deserialize = "unfreeze"))]
+    Unfreeze,
+    /// Administrative reactivation of an account locked by a chargeback.
+    #[serde(rename(serialize = "unlock", deserialize = "unlock"))]
+    Unlock,
+    /// Administrative closing of the clients account, final.
+    #[serde(rename(serialize = "close", deserialize = "close"))]
+    Close,
+}
+
+impl TransactionType {
+    /// Returns true if transactions of this type refer to another
+    /// transaction by their tx instead of introducing a new one.
+    pub fn is_reference(&self) -> bool {
+        matches!(
+            self,
+            Self::Dispute
+                | Self::Resolve
+                | Self::Chargeback
+                | Self::Capture
+                | Self::Void
+                | Self::Expire
+        )
+    }
+
+    /// Returns true if transactions of this type move an amount that can be
+    /// disputed, depending on the
+    /// [DisputePolicy](crate::models::DisputePolicy).
+    pub fn is_disputable(&self) -> bool {
+        matches!(self, Self::Deposit | Self::Withdrawal | Self::Transfer)
+    }
+
+    /// Returns true if transactions of this type change the state of the
+    /// account instead of its amounts.
+    pub fn is_admin(&self) -> bool {
+        matches!(
+            self,
+            Self::Freeze | Self::Unfreeze | Self::Unlock | Self::Close
+        )
+    }
 }
 
 /// Defines a transaction.
 /// Must not change after its creation, so all fields need to be private.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Transaction {
     #[serde(rename(deserialize = "type", serialize = "type"))]
     transaction_type: TransactionType,
     client: AccountId,
     tx: TransactionId,
-    #[serde(serialize_with = "serialize_amount")]
-    amount: Option<f32>,
+    amount: Option<CreditAmount>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    to: Option<AccountId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
 }
 
 impl Transaction {
@@ -48,15 +108,56 @@ impl Transaction {
         transaction_type: TransactionType,
         client: AccountId,
         tx: TransactionId,
-        amount: Option<f32>,
+        amount: Option<CreditAmount>,
     ) -> Self {
         Self {
             transaction_type,
             client,
             tx,
             amount,
+            to: None,
+            reason: None,
         }
     }
+
+    /// Sets the client that receives the amount of a transfer. The recipient
+    /// may belong to another dealer than the client.
+    /// # Example
+    /// ```rust
+    /// use athanasia::{models::*, Engine};
+    /// let amount = |v: &str| v.parse().ok();
+    /// // client 1 and 2 are processed by different dealers
+    /// let transactions = futures::stream::iter(vec![
+    ///     Transaction::new(TransactionType::Deposit, 1, 1, amount("10")),
+    ///     Transaction::new(TransactionType::Transfer, 1, 2, amount("4"))
+    ///         .with_recipient(2),
+    ///     Transaction::new(TransactionType::Dispute, 1, 2, None),
+    ///     Transaction::new(TransactionType::Chargeback, 1, 2, None),
+    /// ]);
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// let accounts = rt
+    ///     .block_on(Engine::new(2).process_stream(transactions))
+    ///     .unwrap();
+    /// let (sender, recipient) = (&accounts[0], &accounts[1]);
+    /// assert_eq!(sender.id(), 1);
+    /// assert_eq!(sender.available(), "10".parse().unwrap());
+    /// assert!(!sender.locked());
+    /// assert_eq!(recipient.id(), 2);
+    /// assert!(recipient.total().is_zero());
+    /// assert!(recipient.held().is_zero());
+    /// assert!(recipient.locked());
+    /// ```
+    pub fn with_recipient(mut self, recipient: AccountId) -> Self {
+        self.to = Some(recipient);
+        self
+    }
+
+    /// Sets the reason of an administrative transaction.
+    pub fn with_reason<S: Into<String>>(mut self, reason: S) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
     /// Returns the transaction type.
     pub fn transaction_type(&self) -> &TransactionType {
         &self.transaction_type
@@ -73,17 +174,17 @@ impl Transaction {
     }
 
     /// Returns the amount.
-    pub fn amount(&self) -> Option<f32> {
+    pub fn amount(&self) -> Option<CreditAmount> {
         self.amount
     }
-}
 
-fn serialize_amount<S>(x: &Option<f32>, s: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    match x {
-        Some(v) => s.serialize_str(&format!("{:.4}", v)),
-        None => s.serialize_str(""),
+    /// Returns the client that receives the amount of a transfer.
+    pub fn recipient(&self) -> Option<AccountId> {
+        self.to
+    }
+
+    /// Returns the reason of an administrative transaction.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
     }
 }
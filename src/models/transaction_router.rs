@@ -0,0 +1,314 @@
+use {
+    crate::models::{
+        AccountId,
+        FileSource,
+        OutcomeReport,
+        ProcessingError,
+        Transaction,
+        TransactionId,
+        TransactionSource,
+        TransactionStream,
+        TransactionType,
+    },
+    crate::models::transaction_stream::Rendezvous,
+    futures_util::{
+        pin_mut,
+        stream::{Stream, StreamExt},
+    },
+    log::{debug, error, warn},
+    std::{
+        collections::{hash_map::Entry, HashMap},
+        str::FromStr,
+        sync::{Arc, Mutex},
+    },
+};
+
+/// Defines how transactions are handled whose tx has already been seen.
+/// Duplicates never reach the accounts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// The duplicate is rejected and reported like any other rejected
+    /// transaction.
+    #[default]
+    Reject,
+    /// The duplicate is dropped without being processed or logged, it is
+    /// only listed as ignored in the
+    /// [OutcomeReport](crate::models::OutcomeReport).
+    Ignore,
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(Self::Reject),
+            "ignore" => Ok(Self::Ignore),
+            _ => Err(format!("Unknown duplicate policy: {}", s)),
+        }
+    }
+}
+
+/// Returns the index of the shard that is responsible for the client.
+pub(crate) fn shard_index(client: &AccountId, shard_count: usize) -> usize {
+    *client as usize % shard_count
+}
+
+/// Splits the incoming transactions by client id onto the streams of the
+/// dealers. All transactions of one client end up on the same stream, so
+/// they are processed in order while different clients are processed in
+/// parallel.
+/// The router is the single point all transactions pass, so it detects
+/// duplicate tx ids across all dealers.
+/// Transactions that affect clients of two dealers, ie. transfers and
+/// disputes of transfers, are pushed onto both streams. The dealers meet at
+/// that position of their streams, so the transaction is applied after all
+/// previous and before all following transactions of both clients.
+pub struct TransactionRouter {
+    streams: Vec<Arc<TransactionStream>>,
+    duplicate_policy: DuplicatePolicy,
+    /// Clients of all routed transactions by tx.
+    seen: Mutex<HashMap<TransactionId, AccountId>>,
+    /// Recipients of all routed transfers by tx.
+    transfers: Mutex<HashMap<TransactionId, AccountId>>,
+    outcome_report: Option<Arc<OutcomeReport>>,
+}
+
+impl TransactionRouter {
+    /// Creates a new router that distributes onto the given streams.
+    pub fn new(streams: Vec<Arc<TransactionStream>>) -> Self {
+        assert!(!streams.is_empty(), "At least one stream is required!");
+        Self {
+            streams,
+            duplicate_policy: DuplicatePolicy::default(),
+            seen: Mutex::new(HashMap::new()),
+            transfers: Mutex::new(HashMap::new()),
+            outcome_report: None,
+        }
+    }
+
+    /// Sets the policy that is applied to duplicate transactions.
+    pub fn with_duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = policy;
+        self
+    }
+
+    /// Makes the router add rows that never reach a dealer, eg. invalid
+    /// rows and rejected duplicates, to the given report.
+    pub fn with_outcome_report(mut self, report: Arc<OutcomeReport>) -> Self {
+        self.outcome_report = Some(report);
+        self
+    }
+
+    /// Returns the index of the shard that is responsible for the given
+    /// client.
+    /// # Example
+    /// ```rust
+    /// use {
+    ///     athanasia::models::{TransactionRouter, TransactionStream},
+    ///     std::sync::Arc,
+    /// };
+    /// let streams = (0..3).map(|_| Arc::new(TransactionStream::new()));
+    /// let router = TransactionRouter::new(streams.collect());
+    /// assert_eq!(router.shard(&7), 1);
+    /// assert_eq!(router.shard(&9), 0);
+    /// ```
+    pub fn shard(&self, client: &AccountId) -> usize {
+        shard_index(client, self.streams.len())
+    }
+
+    /// Remembers the transaction as already routed, eg. for transactions
+    /// that have been processed in a previous run.
+    pub fn mark_seen(&self, transaction: &Transaction) {
+        if transaction.transaction_type().is_reference() {
+            return;
+        }
+        self.seen
+            .lock()
+            .unwrap()
+            .entry(transaction.tx())
+            .or_insert_with(|| transaction.client());
+        if let Some(to) = transaction.recipient() {
+            self.transfers.lock().unwrap().insert(transaction.tx(), to);
+        }
+    }
+
+    /// Pushes the transaction onto the stream of the responsible dealer.
+    /// Transactions with a tx that has already been routed are not pushed,
+    /// they are rejected depending on the [DuplicatePolicy]. References to a
+    /// transaction of another client are rejected, regardless of the dealers
+    /// of the clients.
+    /// # Example
+    /// ```rust
+    /// use {
+    ///     athanasia::models::*,
+    ///     std::sync::Arc,
+    /// };
+    /// let router = TransactionRouter::new(vec![Arc::new(TransactionStream::new())]);
+    /// let amount = "1.0".parse().ok();
+    /// let t = || Transaction::new(TransactionType::Deposit, 1, 1, amount);
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///     assert_eq!(router.route(t()).await, Ok(()));
+    ///     assert_eq!(
+    ///         router.route(t()).await,
+    ///         Err(ProcessingError::DuplicateTransaction)
+    ///     );
+    ///     let dispute = Transaction::new(TransactionType::Dispute, 2, 1, None);
+    ///     assert_eq!(
+    ///         router.route(dispute).await,
+    ///         Err(ProcessingError::ClientMismatch)
+    ///     );
+    /// });
+    /// ```
+    pub async fn route(
+        &self,
+        transaction: Transaction,
+    ) -> Result<(), ProcessingError> {
+        if transaction.transaction_type().is_reference() {
+            let seen = self.seen.lock().unwrap();
+            match seen.get(&transaction.tx()) {
+                Some(client) if *client != transaction.client() => {
+                    #[cfg(debug_assertions)]
+                    debug!("Client ids do not match: {:?}", transaction);
+                    return Err(ProcessingError::ClientMismatch);
+                }
+                _ => (),
+            }
+        } else {
+            let new = match self.seen.lock().unwrap().entry(transaction.tx()) {
+                Entry::Occupied(_) => false,
+                Entry::Vacant(entry) => {
+                    entry.insert(transaction.client());
+                    true
+                }
+            };
+            if !new {
+                return match self.duplicate_policy {
+                    DuplicatePolicy::Reject => {
+                        Err(ProcessingError::DuplicateTransaction)
+                    }
+                    DuplicatePolicy::Ignore => {
+                        #[cfg(debug_assertions)]
+                        debug!("Duplicate ignored: {:?}", transaction);
+                        if let Some(report) = &self.outcome_report {
+                            if let Err(e) = report.record_ignored(&transaction) {
+                                error!(
+                                    "Transaction {} not reported: {}",
+                                    transaction.tx(),
+                                    e
+                                );
+                            }
+                        }
+                        Ok(())
+                    }
+                };
+            }
+        }
+        let counterparty = match transaction.transaction_type() {
+            TransactionType::Transfer => {
+                let to = transaction.recipient();
+                if let Some(to) = to {
+                    self.transfers.lock().unwrap().insert(transaction.tx(), to);
+                }
+                to
+            }
+            t if t.is_reference() => {
+                self.transfers.lock().unwrap().get(&transaction.tx()).copied()
+            }
+            _ => None,
+        };
+        let shard = self.shard(&transaction.client());
+        match counterparty.map(|c| self.shard(&c)).filter(|s| *s != shard) {
+            Some(other) => {
+                let (leader, follower) = Rendezvous::pair();
+                self.streams[shard]
+                    .push_rendezvous(transaction.clone(), leader)
+                    .await;
+                self.streams[other]
+                    .push_rendezvous(transaction, follower)
+                    .await;
+            }
+            None => self.streams[shard].push(transaction).await,
+        }
+        Ok(())
+    }
+
+    /// Reads all transactions from the given file and routes them. Rows that
+    /// do not form a valid transaction are rejected and logged. All streams
+    /// are closed afterwards, even if the file could not be read.
+    pub async fn stream_from_file(&self, name: &str) -> Result<(), std::io::Error> {
+        self.stream_from_source(FileSource::new(name)).await
+    }
+
+    /// Reads all rows of the given source and routes the transactions. Rows
+    /// that do not form a valid transaction are rejected and logged. Reading
+    /// stops at the first error of the source. All streams are closed
+    /// afterwards.
+    pub async fn stream_from_source<S: TransactionSource>(
+        &self,
+        source: S,
+    ) -> Result<(), std::io::Error> {
+        let result = self.read(source).await;
+        self.close();
+        result
+    }
+
+    async fn read<S: TransactionSource>(
+        &self,
+        source: S,
+    ) -> Result<(), std::io::Error> {
+        let mut rows = Box::new(source).rows();
+        while let Some(row) = rows.next().await {
+            match row? {
+                Ok(t) => self.dispatch(t).await,
+                Err(rejected) => {
+                    warn!("Row rejected: {}", rejected.error());
+                    if let Some(report) = &self.outcome_report {
+                        if let Err(e) = report.record_invalid(&rejected) {
+                            error!("Row not reported: {}", e);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Routes all transactions of the given stream. All streams are closed
+    /// afterwards.
+    pub async fn stream_from<S>(&self, transactions: S)
+    where
+        S: Stream<Item = Transaction>,
+    {
+        pin_mut!(transactions);
+        while let Some(t) = transactions.next().await {
+            self.dispatch(t).await;
+        }
+        self.close();
+    }
+
+    /// Routes the transaction, a rejection is logged and reported.
+    async fn dispatch(&self, transaction: Transaction) {
+        let tx = transaction.tx();
+        let copy = self.outcome_report.as_ref().map(|_| transaction.clone());
+        let result = self.route(transaction).await;
+        if let Err(e) = &result {
+            warn!("Transaction {} ignored: {}", tx, e);
+        }
+        if let (Some(report), Some(t), Err(e)) =
+            (&self.outcome_report, &copy, result)
+        {
+            if let Err(e) = report.record(t, &Err(e)) {
+                error!("Transaction {} not reported: {}", tx, e);
+            }
+        }
+    }
+
+    /// Closes all streams.
+    pub fn close(&self) {
+        for s in &self.streams {
+            s.close();
+        }
+    }
+}
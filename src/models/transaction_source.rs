@@ -0,0 +1,427 @@
+use {
+    crate::models::{
+        AccountId,
+        Format,
+        Transaction,
+        TransactionId,
+        TransactionRecord,
+        TransactionType,
+        ValidationError,
+    },
+    async_stream::stream,
+    futures_util::stream::{self, BoxStream, StreamExt},
+    serde_json::Value,
+    std::{
+        convert::TryFrom,
+        io::{self, BufRead, Read},
+        iter,
+        path::PathBuf,
+    },
+    tokio::{
+        io::{AsyncRead, AsyncReadExt},
+        runtime::Handle,
+        sync::mpsc,
+        task,
+    },
+};
+
+/// Count of parsed rows that are queued until the engine takes them.
+const ROW_QUEUE_CAPACITY: usize = 1024;
+
+/// A single line of JSON Lines input. The fields match the csv columns, the
+/// amount may be given as string or number.
+#[derive(Deserialize, Debug)]
+struct JsonRecord {
+    #[serde(rename(deserialize = "type"))]
+    transaction_type: TransactionType,
+    client: AccountId,
+    tx: TransactionId,
+    #[serde(default)]
+    amount: Option<Value>,
+    #[serde(default)]
+    to: Option<AccountId>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// An input row that does not form a valid transaction.
+#[derive(Debug)]
+pub struct RejectedRow {
+    record: Option<TransactionRecord>,
+    error: ValidationError,
+}
+
+impl RejectedRow {
+    /// Creates a new instance. The record is not available if the row could
+    /// not be read at all.
+    pub fn new(record: Option<TransactionRecord>, error: ValidationError) -> Self {
+        Self { record, error }
+    }
+
+    /// Returns the row as it has been read, if available.
+    pub fn record(&self) -> Option<&TransactionRecord> {
+        self.record.as_ref()
+    }
+
+    /// Returns the reason why the row has been rejected.
+    pub fn error(&self) -> &ValidationError {
+        &self.error
+    }
+}
+
+/// A single row of a source, either a valid transaction or the reason why it
+/// has been rejected.
+pub type SourceRow = Result<Transaction, RejectedRow>;
+
+/// Provides the transactions that are processed by the engine. The stream
+/// ends at the first error of the underlying reader, rows that do not form a
+/// valid transaction are returned as [RejectedRow] and do not end the stream.
+pub trait TransactionSource: Send {
+    /// Returns all rows of the source in order.
+    fn rows(self: Box<Self>) -> BoxStream<'static, io::Result<SourceRow>>;
+}
+
+impl<S: TransactionSource + ?Sized> TransactionSource for Box<S> {
+    fn rows(self: Box<Self>) -> BoxStream<'static, io::Result<SourceRow>> {
+        (*self).rows()
+    }
+}
+
+/// Reads csv including the header, or JSON Lines, from any [AsyncRead], eg.
+/// a socket or the output of another process. The input is parsed on a
+/// blocking thread, so the runtime is not blocked while parsing.
+pub struct AsyncReadSource<R> {
+    reader: R,
+    format: Format,
+}
+
+impl<R> AsyncReadSource<R>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    /// Creates a new source reading from the given reader.
+    /// # Example
+    /// ```rust
+    /// use {athanasia::models::*, futures::StreamExt};
+    /// let input: &[u8] = b"type,client,tx,amount\ndeposit,1,1,2.0\ndispute,1,1,0\n";
+    /// let source = Box::new(AsyncReadSource::new(input));
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// let rows: Vec<_> = rt.block_on(source.rows().collect());
+    /// assert_eq!(rows.len(), 2);
+    /// assert!(rows[0].as_ref().unwrap().is_ok());
+    /// let rejected = rows[1].as_ref().unwrap().as_ref().unwrap_err();
+    /// assert_eq!(rejected.error(), &ValidationError::ZeroAmount);
+    /// assert_eq!(rejected.record().unwrap().tx(), 1);
+    /// ```
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            format: Format::Csv,
+        }
+    }
+
+    /// Sets the format of the input. Defaults to [Format::Csv].
+    /// # Example
+    /// ```rust
+    /// use {athanasia::models::*, futures::StreamExt};
+    /// let input: &[u8] = b"{\"type\":\"deposit\",\"client\":1,\"tx\":1,\"amount\":2.5}\n";
+    /// let source = AsyncReadSource::new(input).with_format(Format::JsonLines);
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// let rows: Vec<_> = rt.block_on(Box::new(source).rows().collect());
+    /// let t = rows[0].as_ref().unwrap().as_ref().unwrap();
+    /// assert_eq!(t.amount(), "2.5".parse().ok());
+    /// ```
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+impl<R> TransactionSource for AsyncReadSource<R>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    fn rows(self: Box<Self>) -> BoxStream<'static, io::Result<SourceRow>> {
+        let Self { reader, format } = *self;
+        read_blocking(
+            move || BlockingReader {
+                reader,
+                handle: Handle::current(),
+            },
+            format,
+        )
+    }
+}
+
+/// Reads a file without blocking the runtime. The format is determined by
+/// the file extension, unknown extensions are read as csv.
+pub struct FileSource {
+    path: PathBuf,
+    format: Option<Format>,
+}
+
+impl FileSource {
+    /// Creates a new source reading the given file. The file is opened when
+    /// the rows are requested.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            format: None,
+        }
+    }
+
+    /// Sets the format of the file regardless of its extension.
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+}
+
+impl TransactionSource for FileSource {
+    fn rows(self: Box<Self>) -> BoxStream<'static, io::Result<SourceRow>> {
+        let format = self
+            .format
+            .or_else(|| Format::from_path(&self.path))
+            .unwrap_or_default();
+        Box::pin(stream! {
+            match tokio::fs::File::open(&self.path).await {
+                Ok(file) => {
+                    let source = AsyncReadSource::new(file).with_format(format);
+                    let mut rows = Box::new(source).rows();
+                    while let Some(row) = rows.next().await {
+                        yield row;
+                    }
+                }
+                Err(e) => yield Err(e),
+            }
+        })
+    }
+}
+
+/// Reads from stdin, eg. `athanasia - < tx.csv`.
+#[derive(Default)]
+pub struct StdinSource {
+    format: Format,
+}
+
+impl StdinSource {
+    /// Creates a new source reading csv from stdin.
+    pub fn new() -> Self {
+        Self {
+            format: Format::Csv,
+        }
+    }
+
+    /// Sets the format of the input. Defaults to [Format::Csv].
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+impl TransactionSource for StdinSource {
+    fn rows(self: Box<Self>) -> BoxStream<'static, io::Result<SourceRow>> {
+        let source =
+            AsyncReadSource::new(tokio::io::stdin()).with_format(self.format);
+        Box::new(source).rows()
+    }
+}
+
+/// Reads csv including the header, or JSON Lines, from a blocking [Read],
+/// eg. in-memory data. The input is read and parsed on a blocking thread, so
+/// the runtime is not blocked while waiting for the reader.
+pub struct ReaderSource<R> {
+    reader: R,
+    format: Format,
+}
+
+impl<R> ReaderSource<R>
+where
+    R: Read + Send + 'static,
+{
+    /// Creates a new source reading csv from the given reader.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            format: Format::Csv,
+        }
+    }
+
+    /// Sets the format of the input. Defaults to [Format::Csv].
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+impl<R> TransactionSource for ReaderSource<R>
+where
+    R: Read + Send + 'static,
+{
+    fn rows(self: Box<Self>) -> BoxStream<'static, io::Result<SourceRow>> {
+        let Self { reader, format } = *self;
+        read_blocking(move || reader, format)
+    }
+}
+
+/// Provides already parsed transactions, eg. from memory.
+pub struct IteratorSource<I> {
+    transactions: I,
+}
+
+impl<I> IteratorSource<I>
+where
+    I: Iterator<Item = Transaction> + Send + 'static,
+{
+    /// Creates a new source providing the given transactions.
+    pub fn new<T>(transactions: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        Self {
+            transactions: transactions.into_iter(),
+        }
+    }
+}
+
+impl<I> TransactionSource for IteratorSource<I>
+where
+    I: Iterator<Item = Transaction> + Send + 'static,
+{
+    fn rows(self: Box<Self>) -> BoxStream<'static, io::Result<SourceRow>> {
+        Box::pin(stream::iter(self.transactions.map(|t| Ok(Ok(t)))))
+    }
+}
+
+/// Parses a single line of JSON Lines input.
+fn parse_json(line: u64, text: &str) -> SourceRow {
+    let record: JsonRecord = serde_json::from_str(text).map_err(|e| {
+        RejectedRow::new(
+            None,
+            ValidationError::Malformed(format!("line {}: {}", line, e)),
+        )
+    })?;
+    let amount = match record.amount {
+        None | Some(Value::Null) => None,
+        Some(Value::String(s)) => Some(s),
+        Some(Value::Number(n)) => Some(n.to_string()),
+        Some(v) => {
+            return Err(RejectedRow::new(
+                None,
+                ValidationError::Malformed(format!(
+                    "line {}: amount must be a string or number, found {}",
+                    line, v
+                )),
+            ))
+        }
+    };
+    parse(TransactionRecord::new(
+        record.transaction_type,
+        record.client,
+        record.tx,
+        amount,
+    )
+    .with_recipient(record.to)
+    .with_reason(record.reason))
+}
+
+fn json_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "JSON input is not supported, use JSON Lines",
+    )
+}
+
+/// Reads all rows of csv including the header, or JSON Lines, from the given
+/// reader. Ends after the first error of the reader, the remaining rows are
+/// not available.
+fn read_rows<R>(
+    reader: R,
+    format: Format,
+) -> Box<dyn Iterator<Item = io::Result<SourceRow>> + Send>
+where
+    R: Read + Send + 'static,
+{
+    let rows: Box<dyn Iterator<Item = io::Result<SourceRow>> + Send> =
+        match format {
+            Format::Json => Box::new(iter::once(Err(json_unsupported()))),
+            Format::JsonLines => Box::new(
+                io::BufReader::new(reader).lines().enumerate().filter_map(
+                    |(i, line)| match line {
+                        Ok(line) if line.trim().is_empty() => None,
+                        Ok(line) => Some(Ok(parse_json(i as u64 + 1, &line))),
+                        Err(e) => Some(Err(e)),
+                    },
+                ),
+            ),
+            Format::Csv => Box::new(
+                csv::ReaderBuilder::new()
+                    .trim(csv::Trim::All)
+                    .from_reader(reader)
+                    .into_deserialize::<TransactionRecord>()
+                    .map(|result| match result {
+                        Ok(record) => Ok(parse(record)),
+                        Err(e) if e.is_io_error() => Err(e.into()),
+                        Err(e) => Ok(Err(malformed(e))),
+                    }),
+            ),
+        };
+    let mut failed = false;
+    Box::new(rows.take_while(move |row| {
+        let more = !failed;
+        failed = row.is_err();
+        more
+    }))
+}
+
+/// Reads and parses the rows of the reader returned by `open` on a blocking
+/// thread and passes them to the returned stream. `open` is called on that
+/// thread as soon as the stream is polled for the first time.
+fn read_blocking<R, F>(
+    open: F,
+    format: Format,
+) -> BoxStream<'static, io::Result<SourceRow>>
+where
+    R: Read + Send + 'static,
+    F: FnOnce() -> R + Send + 'static,
+{
+    Box::pin(stream! {
+        let (sender, mut receiver) = mpsc::channel(ROW_QUEUE_CAPACITY);
+        let parser = task::spawn_blocking(move || {
+            for row in read_rows(open(), format) {
+                // the stream has been dropped
+                if sender.blocking_send(row).is_err() {
+                    break;
+                }
+            }
+        });
+        while let Some(row) = receiver.recv().await {
+            yield row;
+        }
+        if let Err(e) = parser.await {
+            yield Err(io::Error::new(io::ErrorKind::Other, e));
+        }
+    })
+}
+
+/// Reads from an [AsyncRead] on a blocking thread, so the input can be
+/// parsed by a single blocking csv reader.
+struct BlockingReader<R> {
+    reader: R,
+    handle: Handle,
+}
+
+impl<R: AsyncRead + Unpin> Read for BlockingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let reader = &mut self.reader;
+        self.handle.block_on(reader.read(buf))
+    }
+}
+
+fn parse(record: TransactionRecord) -> SourceRow {
+    Transaction::try_from(&record).map_err(|e| RejectedRow::new(Some(record), e))
+}
+
+fn malformed(e: csv::Error) -> RejectedRow {
+    RejectedRow::new(None, ValidationError::Malformed(e.to_string()))
+}
@@ -1,86 +1,146 @@
 use {
-    crate::models::{Transaction, TransactionQueue},
-    async_stream::stream,
-    csv,
-    futures_util::{pin_mut, stream::StreamExt},
-    log::{error},
-    std::{sync::Arc, path::Path, 
-        io::{BufReader},
-        fs::File,
-    },
-    tokio::{
-        sync::{RwLock}
-    },
+    crate::models::Transaction,
+    std::sync::{Arc, RwLock},
+    tokio::sync::{mpsc, Barrier, Mutex},
 };
 
-/// Abstration of an incoming stream of transaction.
+/// Default count of transactions a stream holds before the reader has to wait.
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Synchronizes the two dealers that are involved in a transaction affecting
+/// clients of both, eg. a transfer. The transaction is pushed onto both
+/// streams. The leader applies it while the other dealer waits at the same
+/// position of its stream, so both streams keep the order of the input.
+pub(crate) struct Rendezvous {
+    barrier: Arc<Barrier>,
+    leader: bool,
+}
+
+impl Rendezvous {
+    /// Creates the rendezvous of the leader and of the other dealer.
+    pub(crate) fn pair() -> (Self, Self) {
+        let barrier = Arc::new(Barrier::new(2));
+        let leader = Self {
+            barrier: barrier.clone(),
+            leader: true,
+        };
+        (leader, Self {
+            barrier,
+            leader: false,
+        })
+    }
+
+    /// Returns true for the dealer that applies the transaction.
+    pub(crate) fn is_leader(&self) -> bool {
+        self.leader
+    }
+
+    /// Waits until the other dealer has reached the rendezvous as well.
+    pub(crate) async fn wait(&self) {
+        self.barrier.wait().await;
+    }
+}
+
+type Item = (Transaction, Option<Rendezvous>);
+
+/// Abstration of an incoming stream of transaction. Every dealer owns one
+/// stream that only contains transactions of the clients it is responsible
+/// for.
+/// The stream is bounded, pushing onto a full stream waits until the dealer
+/// took a transaction, so the memory usage stays flat regardless of the
+/// input size. Waiting for the next transaction does not use any CPU time,
+/// the dealer is woken up as soon as a transaction has been pushed.
 pub struct TransactionStream {
-    alive: bool,
-    transaction_queue: Arc<RwLock<TransactionQueue>>,
+    sender: RwLock<Option<mpsc::Sender<Item>>>,
+    receiver: Mutex<mpsc::Receiver<Item>>,
+}
+
+impl Default for TransactionStream {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TransactionStream {
+    /// Creates a new, empty transaction stream with [DEFAULT_CAPACITY].
     pub fn new() -> Self {
-        let transaction_queue = Arc::new(RwLock::new(TransactionQueue::new()));
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Creates a new, empty transaction stream that holds at most `capacity`
+    /// transactions. A capacity of zero is treated as one.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
         Self {
-            alive: true,
-            transaction_queue,
+            sender: RwLock::new(Some(sender)),
+            receiver: Mutex::new(receiver),
         }
     }
 
-    /// Creates a new instance that reads from the given file.
-    pub async fn stream_from_file(&mut self, name: &str) -> Result<(), std::io::Error> {
-        let file = File::open(Path::new(name))?;
-        let mut reader = csv::ReaderBuilder::new()
-            .trim(csv::Trim::All)
-            .from_reader(BufReader::new(file));
-        let s = stream! {
-            for result in reader.deserialize() {
-                let transaction: Transaction = match result {
-                    Ok(t) => t,
-                    Err(msg) => {
-                        error!("{}", msg.to_string());
-                        continue;
-                    },
-                };
-                yield transaction;
-            }
-        };
-        pin_mut!(s);
-
-        while let Some(t) = s.next().await {
-            let mut q = self.transaction_queue.write().await;
-            q.push(t);
-        }
+    /// Appends the given transaction to the end of the stream. Waits until
+    /// there is a free slot if the stream is full. Transactions pushed onto a
+    /// closed stream are dropped.
+    /// # Example
+    /// ```rust
+    /// use {
+    ///     athanasia::models::{Transaction, TransactionStream, TransactionType},
+    ///     std::time::Duration,
+    /// };
+    /// let stream = TransactionStream::with_capacity(1);
+    /// let t = |tx| Transaction::new(TransactionType::Deposit, 1, tx, None);
+    /// let rt = tokio::runtime::Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///     stream.push(t(1)).await;
+    ///     let full = tokio::time::timeout(Duration::from_millis(10), stream.push(t(2)));
+    ///     assert!(full.await.is_err());
+    ///     assert_eq!(stream.next().await.unwrap().tx(), 1);
+    ///     stream.push(t(2)).await;
+    ///     stream.close();
+    ///     assert_eq!(stream.next().await.unwrap().tx(), 2);
+    ///     assert!(stream.next().await.is_none());
+    /// });
+    /// ```
+    pub async fn push(&self, transaction: Transaction) {
+        self.push_item((transaction, None)).await
+    }
 
-        self.alive = false;
-        Ok(())
+    /// Appends the given transaction together with the rendezvous of the
+    /// dealer.
+    pub(crate) async fn push_rendezvous(
+        &self,
+        transaction: Transaction,
+        rendezvous: Rendezvous,
+    ) {
+        self.push_item((transaction, Some(rendezvous))).await
     }
 
-    pub async fn next(&mut self) -> Option<Transaction> {
-        let nothing_available = {
-            let q = self.transaction_queue.read().await;
-            q.len() == 0
+    async fn push_item(&self, item: Item) {
+        let sender = match &*self.sender.read().unwrap() {
+            Some(s) => s.clone(),
+            None => return,
         };
-        if nothing_available {
-            return None;
-        }
-        let mut q = self.transaction_queue.write().await;
-        Some(q.remove(0))
+        // the receiver lives as long as the stream
+        let _ = sender.send(item).await;
     }
 
-    /// Returns an Arc to the transaction queue.
-    pub fn transaction_queue(&self) -> Arc<RwLock<TransactionQueue>> {
-        self.transaction_queue.clone()
+    /// Waits for the next transaction. Returns `None` as soon as the stream
+    /// has been closed and all transactions have been taken.
+    pub async fn next(&self) -> Option<Transaction> {
+        self.next_item().await.map(|(t, _)| t)
     }
 
-    /// Tells if the stream is still alive.
-    pub fn alive(&self) -> bool {
-        self.alive
+    /// Waits for the next transaction and its rendezvous, if any.
+    pub(crate) async fn next_item(&self) -> Option<Item> {
+        self.receiver.lock().await.recv().await
     }
 
-    /// Returns true if the transaction queue is empty.
-    pub async fn empty(&self) -> bool {
-        self.transaction_queue.read().await.len() == 0
+    /// Marks the stream as finished, no further transactions will be pushed.
+    pub fn close(&self) {
+        self.sender.write().unwrap().take();
+    }
+
+    /// Tells if the stream is still alive.
+    pub fn alive(&self) -> bool {
+        self.sender.read().unwrap().is_some()
     }
 }
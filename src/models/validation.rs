@@ -0,0 +1,252 @@
+use {
+    crate::models::{
+        AccountId,
+        AmountError,
+        CreditAmount,
+        Transaction,
+        TransactionId,
+        TransactionType,
+    },
+    std::{convert::TryFrom, fmt},
+};
+
+/// Reason why an input row does not form a valid transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The row could not be read, eg. because of an unknown transaction type.
+    Malformed(String),
+    /// The transaction type requires an amount, but none was given.
+    MissingAmount,
+    /// The amount is not a valid decimal number or has too many places.
+    InvalidAmount(AmountError),
+    /// The transaction type does not take an amount, but one was given.
+    UnexpectedAmount,
+    /// The amount is lower than zero.
+    NegativeAmount,
+    /// The amount of a dispute, resolve or chargeback is zero.
+    ZeroAmount,
+    /// The transaction type requires a recipient, but none was given.
+    MissingRecipient,
+    /// The transaction type does not take a recipient, but one was given.
+    UnexpectedRecipient,
+    /// The recipient is the client itself.
+    SelfTransfer,
+    /// The transaction type changes the state of the account, but no reason
+    /// was given.
+    MissingReason,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(msg) => write!(f, "malformed row: {}", msg),
+            Self::MissingAmount => write!(f, "amount is missing"),
+            Self::InvalidAmount(e) => write!(f, "{}", e),
+            Self::UnexpectedAmount => {
+                write!(f, "transaction type does not take an amount")
+            }
+            Self::NegativeAmount => write!(f, "amount is negative"),
+            Self::ZeroAmount => write!(f, "amount is zero"),
+            Self::MissingRecipient => write!(f, "recipient is missing"),
+            Self::UnexpectedRecipient => {
+                write!(f, "transaction type does not take a recipient")
+            }
+            Self::SelfTransfer => write!(f, "recipient is the client itself"),
+            Self::MissingReason => write!(f, "reason is missing"),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// A single row of the input, before it has been validated.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TransactionRecord {
+    #[serde(rename(deserialize = "type"))]
+    transaction_type: TransactionType,
+    client: AccountId,
+    tx: TransactionId,
+    amount: Option<String>,
+    #[serde(default)]
+    to: Option<AccountId>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+impl TransactionRecord {
+    /// Creates a new row, the amount is given as it has been read.
+    pub fn new(
+        transaction_type: TransactionType,
+        client: AccountId,
+        tx: TransactionId,
+        amount: Option<String>,
+    ) -> Self {
+        Self {
+            transaction_type,
+            client,
+            tx,
+            amount,
+            to: None,
+            reason: None,
+        }
+    }
+
+    /// Sets the recipient of the row.
+    pub fn with_recipient(mut self, recipient: Option<AccountId>) -> Self {
+        self.to = recipient;
+        self
+    }
+
+    /// Sets the reason of the row.
+    pub fn with_reason(mut self, reason: Option<String>) -> Self {
+        self.reason = reason;
+        self
+    }
+
+    /// Returns the transaction type of the row.
+    pub fn transaction_type(&self) -> &TransactionType {
+        &self.transaction_type
+    }
+
+    /// Returns the client of the row.
+    pub fn client(&self) -> AccountId {
+        self.client
+    }
+
+    /// Returns the tx of the row.
+    pub fn tx(&self) -> TransactionId {
+        self.tx
+    }
+
+    /// Returns the amount of the row as it has been read.
+    pub fn amount(&self) -> Option<&str> {
+        self.amount.as_deref()
+    }
+
+    /// Returns the recipient of the row.
+    pub fn recipient(&self) -> Option<AccountId> {
+        self.to
+    }
+
+    /// Returns the reason of the row.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+}
+
+impl TryFrom<&TransactionRecord> for Transaction {
+    type Error = ValidationError;
+
+    /// Parses the amount and checks the row using [validate].
+    fn try_from(record: &TransactionRecord) -> Result<Self, Self::Error> {
+        let amount = match record.amount.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(v) => Some(
+                v.parse::<CreditAmount>()
+                    .map_err(ValidationError::InvalidAmount)?,
+            ),
+        };
+        let mut transaction = Transaction::new(
+            record.transaction_type,
+            record.client,
+            record.tx,
+            amount,
+        );
+        if let Some(to) = record.to {
+            transaction = transaction.with_recipient(to);
+        }
+        match record.reason.as_deref().map(str::trim) {
+            None | Some("") => (),
+            Some(reason) => transaction = transaction.with_reason(reason),
+        }
+        validate(&transaction)?;
+        Ok(transaction)
+    }
+}
+
+impl TryFrom<TransactionRecord> for Transaction {
+    type Error = ValidationError;
+
+    /// Parses the amount and checks the row using [validate].
+    fn try_from(record: TransactionRecord) -> Result<Self, Self::Error> {
+        Self::try_from(&record)
+    }
+}
+
+/// Checks the field requirements of the transaction type. Deposits,
+/// withdrawals, transfers and authorizations require a non-negative amount.
+/// The dispute types and captures take an optional amount greater than zero
+/// to refer to a part of the referenced transaction, voids and expiries must
+/// not have an amount. Transfers require a recipient other than the
+/// client, all other types must not have a recipient. Administrative types
+/// require a reason and must not have an amount.
+/// # Example
+/// ```rust
+/// use athanasia::models::{validate, Transaction, TransactionType, ValidationError};
+/// let t = Transaction::new(TransactionType::Deposit, 1, 1, None);
+/// assert_eq!(validate(&t), Err(ValidationError::MissingAmount));
+/// let t = Transaction::new(TransactionType::Withdrawal, 1, 1, "-1".parse().ok());
+/// assert_eq!(validate(&t), Err(ValidationError::NegativeAmount));
+/// let t = Transaction::new(TransactionType::Dispute, 1, 1, "-1".parse().ok());
+/// assert_eq!(validate(&t), Err(ValidationError::NegativeAmount));
+/// let t = Transaction::new(TransactionType::Resolve, 1, 1, "0".parse().ok());
+/// assert_eq!(validate(&t), Err(ValidationError::ZeroAmount));
+/// let t = Transaction::new(TransactionType::Chargeback, 1, 1, "1".parse().ok());
+/// assert_eq!(validate(&t), Ok(()));
+/// let t = Transaction::new(TransactionType::Dispute, 1, 1, None);
+/// assert_eq!(validate(&t), Ok(()));
+/// let t = Transaction::new(TransactionType::Transfer, 1, 1, "1".parse().ok());
+/// assert_eq!(validate(&t), Err(ValidationError::MissingRecipient));
+/// assert_eq!(validate(&t.with_recipient(1)), Err(ValidationError::SelfTransfer));
+/// let t = Transaction::new(TransactionType::Freeze, 1, 4, None);
+/// assert_eq!(validate(&t), Err(ValidationError::MissingReason));
+/// assert_eq!(validate(&t.with_reason("fraud suspicion")), Ok(()));
+/// ```
+pub fn validate(transaction: &Transaction) -> Result<(), ValidationError> {
+    match (transaction.transaction_type(), transaction.recipient()) {
+        (TransactionType::Transfer, None) => {
+            return Err(ValidationError::MissingRecipient)
+        }
+        (TransactionType::Transfer, Some(to)) if to == transaction.client() => {
+            return Err(ValidationError::SelfTransfer)
+        }
+        (TransactionType::Transfer, Some(_)) | (_, None) => (),
+        (_, Some(_)) => return Err(ValidationError::UnexpectedRecipient),
+    }
+    match transaction.transaction_type() {
+        TransactionType::Deposit
+        | TransactionType::Withdrawal
+        | TransactionType::Transfer
+        | TransactionType::Authorize => match transaction.amount() {
+            None => Err(ValidationError::MissingAmount),
+            Some(a) if a.is_negative() => Err(ValidationError::NegativeAmount),
+            Some(_) => Ok(()),
+        },
+        TransactionType::Dispute
+        | TransactionType::Resolve
+        | TransactionType::Chargeback
+        | TransactionType::Capture => match transaction.amount() {
+            Some(a) if a.is_negative() => Err(ValidationError::NegativeAmount),
+            Some(a) if a.is_zero() => Err(ValidationError::ZeroAmount),
+            _ => Ok(()),
+        },
+        TransactionType::Void | TransactionType::Expire => {
+            match transaction.amount() {
+                Some(_) => Err(ValidationError::UnexpectedAmount),
+                None => Ok(()),
+            }
+        }
+        TransactionType::Freeze
+        | TransactionType::Unfreeze
+        | TransactionType::Unlock
+        | TransactionType::Close => {
+            if transaction.amount().is_some() {
+                Err(ValidationError::UnexpectedAmount)
+            } else if transaction.reason().is_none() {
+                Err(ValidationError::MissingReason)
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
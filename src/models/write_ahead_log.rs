@@ -0,0 +1,247 @@
+use {
+    crate::models::{
+        rules::seconds,
+        AccountId,
+        CreditAmount,
+        Outcome,
+        Transaction,
+        TransactionId,
+        TransactionType,
+    },
+    std::{
+        fs::{self, File, OpenOptions},
+        io::{self, Read, Seek, SeekFrom},
+        path::{Path, PathBuf},
+        sync::Mutex,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// Name of the log file inside of the log directory.
+pub const WRITE_AHEAD_LOG_FILE: &str = "transactions.wal";
+
+/// A transaction of the log with its outcome and the settings it has been
+/// processed with, so it is replayed the same way even if the settings of
+/// the engine have changed since.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    /// The processed transaction.
+    pub transaction: Transaction,
+    /// Empty if the transaction has been rejected.
+    pub outcome: Option<Outcome>,
+    /// Time the transaction has been processed, empty in older logs.
+    pub processed_at: Option<SystemTime>,
+    /// Credit limit of the client when the transaction has been processed,
+    /// empty for rejected transactions and in older logs.
+    pub credit_limit: Option<CreditAmount>,
+    /// Expiry time of the hold of an authorization, empty if it does not
+    /// expire on its own.
+    pub expires_at: Option<SystemTime>,
+}
+
+impl LogEntry {
+    /// Creates an entry of a transaction that has been rejected at the given
+    /// time.
+    pub fn rejected(transaction: Transaction, processed_at: SystemTime) -> Self {
+        Self {
+            transaction,
+            outcome: None,
+            processed_at: Some(processed_at),
+            credit_limit: None,
+            expires_at: None,
+        }
+    }
+}
+
+/// Size of the chunks that are read when looking for the last complete entry.
+const TAIL_CHUNK_SIZE: u64 = 4096;
+
+/// A single entry of the log, a transaction with its outcome and the time it
+/// has been processed.
+#[derive(Serialize, Deserialize, Debug)]
+struct LogRecord {
+    transaction_type: TransactionType,
+    client: AccountId,
+    tx: TransactionId,
+    amount: Option<CreditAmount>,
+    /// Empty if the transaction has been rejected.
+    outcome: Option<Outcome>,
+    /// Missing in logs written before transfers have been supported.
+    #[serde(default)]
+    to: Option<AccountId>,
+    /// Missing in logs written before state changes have been supported.
+    #[serde(default)]
+    reason: Option<String>,
+    /// Seconds since the unix epoch, missing in logs written before the time
+    /// of processing has been logged.
+    #[serde(default)]
+    processed_at: Option<u64>,
+    /// Missing in logs written before the credit limit has been logged.
+    #[serde(default)]
+    credit_limit: Option<CreditAmount>,
+    /// Seconds since the unix epoch, missing in logs written before
+    /// authorizations could expire.
+    #[serde(default)]
+    expires_at: Option<u64>,
+}
+
+/// Append-only log of all accepted transactions and their outcomes.
+/// Rejected transactions are logged without an outcome, so their tx is still
+/// known as a duplicate after a restart.
+///
+/// Every entry is flushed to the file as soon as it has been appended, so the
+/// log survives a crash of the process. Replaying the entries in order on an
+/// empty storage rebuilds the exact state that was present when the last
+/// entry was written.
+pub struct WriteAheadLog {
+    path: PathBuf,
+    writer: Mutex<csv::Writer<File>>,
+    file: File,
+}
+
+impl WriteAheadLog {
+    /// Opens the log inside of the given directory, the directory and the log
+    /// file are created if required. An incomplete entry at the end of the
+    /// file, left over by a crash while writing, is removed.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let path = dir.as_ref().join(WRITE_AHEAD_LOG_FILE);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+        let len = complete_len(&mut file)?;
+        file.set_len(len)?;
+        let writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(file.try_clone()?);
+        Ok(Self {
+            path,
+            writer: Mutex::new(writer),
+            file,
+        })
+    }
+
+    /// Appends the entry and flushes the log.
+    pub fn append(&self, entry: &LogEntry) -> io::Result<()> {
+        let transaction = &entry.transaction;
+        let record = LogRecord {
+            transaction_type: *transaction.transaction_type(),
+            client: transaction.client(),
+            tx: transaction.tx(),
+            amount: transaction.amount(),
+            outcome: entry.outcome,
+            to: transaction.recipient(),
+            reason: transaction.reason().map(str::to_string),
+            processed_at: entry.processed_at.map(seconds),
+            credit_limit: entry.credit_limit,
+            expires_at: entry.expires_at.map(seconds),
+        };
+        let mut writer = self.writer.lock().unwrap();
+        writer.serialize(record)?;
+        writer.flush()
+    }
+
+    /// Returns all entries of the log in the order they have been appended.
+    /// The outcome of rejected transactions is `None`, the settings are
+    /// `None` for entries of older logs that did not contain them.
+    /// # Example
+    /// ```rust
+    /// use {
+    ///     athanasia::models::*,
+    ///     std::time::{Duration, UNIX_EPOCH},
+    /// };
+    /// let dir = std::env::temp_dir().join("athanasia-wal-doctest");
+    /// let _ = std::fs::remove_dir_all(&dir);
+    /// let log = WriteAheadLog::open(&dir).unwrap();
+    /// let time = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    /// let t = Transaction::new(TransactionType::Deposit, 1, 1, "2.5".parse().ok());
+    /// log.append(&LogEntry {
+    ///     transaction: t,
+    ///     outcome: Some(Outcome::Deposited),
+    ///     processed_at: Some(time),
+    ///     credit_limit: "1".parse().ok(),
+    ///     expires_at: None,
+    /// })
+    /// .unwrap();
+    /// let t = Transaction::new(TransactionType::Withdrawal, 1, 2, "5".parse().ok());
+    /// log.append(&LogEntry::rejected(t, time)).unwrap();
+    ///
+    /// let log = WriteAheadLog::open(&dir).unwrap();
+    /// let entries: Vec<_> = log.entries().unwrap().map(Result::unwrap).collect();
+    /// assert_eq!(entries.len(), 2);
+    /// assert_eq!(entries[0].transaction.amount(), "2.5".parse().ok());
+    /// assert_eq!(entries[0].outcome, Some(Outcome::Deposited));
+    /// assert_eq!(entries[0].processed_at, Some(time));
+    /// assert_eq!(entries[0].credit_limit, "1".parse().ok());
+    /// assert_eq!((entries[1].transaction.tx(), entries[1].outcome), (2, None));
+    /// ```
+    pub fn entries(
+        &self,
+    ) -> io::Result<impl Iterator<Item = io::Result<LogEntry>>> {
+        let reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(&self.path)?;
+        Ok(reader.into_deserialize::<LogRecord>().map(|result| {
+            let record = result?;
+            let mut transaction = Transaction::new(
+                record.transaction_type,
+                record.client,
+                record.tx,
+                record.amount,
+            );
+            if let Some(to) = record.to {
+                transaction = transaction.with_recipient(to);
+            }
+            if let Some(reason) = record.reason {
+                transaction = transaction.with_reason(reason);
+            }
+            Ok(LogEntry {
+                transaction,
+                outcome: record.outcome,
+                processed_at: record.processed_at.map(time),
+                credit_limit: record.credit_limit,
+                expires_at: record.expires_at.map(time),
+            })
+        }))
+    }
+
+    /// Removes all entries, eg. after the state has been saved in a
+    /// [Snapshot](crate::models::Snapshot).
+    pub fn truncate(&self) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.flush()?;
+        self.file.set_len(0)?;
+        self.file.sync_all()
+    }
+
+    /// Makes sure that all entries have been written to the disk.
+    pub fn sync(&self) -> io::Result<()> {
+        self.writer.lock().unwrap().flush()?;
+        self.file.sync_all()
+    }
+}
+
+/// Returns the time of the given seconds since the unix epoch.
+fn time(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+/// Returns the length of the file up to and including its last line break.
+fn complete_len(file: &mut File) -> io::Result<u64> {
+    let mut end = file.metadata()?.len();
+    let mut buf = vec![0; TAIL_CHUNK_SIZE as usize];
+    while end > 0 {
+        let start = end.saturating_sub(TAIL_CHUNK_SIZE);
+        let chunk = &mut buf[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+        if let Some(i) = chunk.iter().rposition(|b| *b == b'\n') {
+            return Ok(start + i as u64 + 1);
+        }
+        end = start;
+    }
+    Ok(0)
+}
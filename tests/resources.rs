@@ -0,0 +1,474 @@
+//! Runs the engine over the csv files of the `resources` folder. Every case
+//! is processed by one and by several dealers, both have to result in the
+//! same accounts.
+
+use {
+    athanasia::{models::*, Engine},
+    std::{
+        path::{Path, PathBuf},
+        time::Duration,
+    },
+};
+
+/// Dealer counts every case is processed with.
+const DEALERS: &[u8] = &[1, 4];
+
+/// Returns the path of the given file of the `resources` folder.
+fn resource(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("resources")
+        .join(name)
+}
+
+/// Returns a path in the temp folder that is not in use by another case.
+fn temp(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("athanasia-test-{}", name));
+    let _ = std::fs::remove_dir_all(&path);
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+/// Processes the resource file and returns the accounts as csv.
+fn run(engine: &Engine, name: &str) -> String {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let accounts = rt.block_on(engine.process_file(resource(name))).unwrap();
+    let mut out = vec![];
+    let mut writer = RecordWriter::new(Format::Csv, &mut out);
+    for account in &accounts {
+        writer.write(account).unwrap();
+    }
+    writer.finish().unwrap();
+    drop(writer);
+    String::from_utf8(out).unwrap()
+}
+
+/// Processes the resource file with every count of [DEALERS], each engine
+/// is set up by `configure`. Asserts the resulting accounts and returns the
+/// outcome report of the last run, sorted by tx, type and the whole line.
+fn assert_accounts<F>(name: &str, configure: F, expected: &str) -> String
+where
+    F: Fn(Engine) -> Engine,
+{
+    let report = temp(&format!("{}.report.csv", name));
+    for dealers in DEALERS {
+        let engine = configure(Engine::new(*dealers)).with_outcome_report(&report);
+        let accounts = run(&engine, name);
+        assert_eq!(accounts, expected, "{} with {} dealers", name, dealers);
+    }
+    let report = std::fs::read_to_string(report).unwrap();
+    let mut lines: Vec<_> = report.lines().skip(1).map(str::to_string).collect();
+    lines.sort_by_key(|l| {
+        let columns: Vec<_> = l.split(',').collect();
+        let tx = columns[2].parse::<u32>().unwrap_or_default();
+        (tx, columns[0].to_string(), l.clone())
+    });
+    lines.join("\n")
+}
+
+#[test]
+fn resolve_and_chargeback_of_admin_transactions_are_rejected() {
+    let report = assert_accounts(
+        "transactions-admin-reference.csv",
+        |e| e,
+        "id,available,held,total,locked,credit_limit\n\
+         1,5.0000,0.0000,5.0000,false,0.0000\n",
+    );
+    assert_eq!(
+        report,
+        "deposit,1,1,5.0000,,deposited,\n\
+         chargeback,1,2,,,rejected,transaction type can not be disputed\n\
+         dispute,1,2,,,rejected,transaction type can not be disputed\n\
+         freeze,1,2,,,frozen,fraud suspicion\n\
+         resolve,1,2,,,rejected,transaction type can not be disputed\n\
+         unfreeze,1,3,,,unfrozen,cleared by compliance"
+    );
+}
+
+#[test]
+fn locked_accounts_settle_authorizations_and_closing_requires_settlement() {
+    let report = assert_accounts(
+        "transactions-locked-holds.csv",
+        |e| e,
+        "id,available,held,total,locked,credit_limit\n\
+         1,9.0000,0.0000,9.0000,true,0.0000\n\
+         2,3.0000,0.0000,3.0000,false,0.0000\n\
+         3,0.0000,0.0000,0.0000,false,0.0000\n\
+         4,5.0000,0.0000,5.0000,false,0.0000\n",
+    );
+    assert_eq!(
+        report,
+        "deposit,1,1,10.0000,,deposited,\n\
+         chargeback,1,2,,,charged_back,\n\
+         deposit,1,2,5.0000,,deposited,\n\
+         dispute,1,2,,,disputed,\n\
+         authorize,1,3,4.0000,,authorized,\n\
+         capture,1,3,1.0000,,captured,\n\
+         void,1,3,,,voided,\n\
+         authorize,1,4,2.0000,,authorized,\n\
+         expire,1,4,,,expired,\n\
+         deposit,2,10,3.0000,,deposited,\n\
+         authorize,2,11,2.0000,,authorized,\n\
+         void,2,11,,,voided,\n\
+         close,2,12,,,rejected,open authorizations or disputes\n\
+         close,2,13,,,closed,closing\n\
+         deposit,3,20,5.0000,,deposited,\n\
+         dispute,3,21,,,disputed,\n\
+         resolve,3,21,,,resolved,\n\
+         transfer,3,21,5.0000,4,transferred,\n\
+         close,3,22,,,rejected,open authorizations or disputes\n\
+         close,3,23,,,closed,closing"
+    );
+}
+
+#[test]
+fn expired_authorizations_are_released_without_an_expire_row() {
+    let report = assert_accounts(
+        "transactions-hold-expiry.csv",
+        |e| e.with_hold_ttl(Duration::from_secs(0)),
+        "id,available,held,total,locked,credit_limit\n\
+         1,11.0000,0.0000,11.0000,false,0.0000\n\
+         2,5.0000,0.0000,5.0000,false,0.0000\n",
+    );
+    assert_eq!(
+        report,
+        "deposit,1,1,10.0000,,deposited,\n\
+         authorize,1,2,4.0000,,authorized,\n\
+         capture,1,2,,,rejected,Capture not allowed on expired authorization\n\
+         deposit,1,3,1.0000,,deposited,\n\
+         deposit,2,10,5.0000,,deposited,\n\
+         authorize,2,11,3.0000,,authorized,"
+    );
+}
+
+#[test]
+fn write_ahead_log_is_replayed_with_the_settings_it_has_been_written_with() {
+    for dealers in DEALERS {
+        let wal = temp(&format!("wal-settings-{}", dealers));
+        let engine = Engine::new(*dealers)
+            .with_write_ahead_log(&wal)
+            .with_credit_limits(resource("accounts-credit-limit.csv"))
+            .with_hold_ttl(Duration::from_secs(3600));
+        assert_eq!(
+            run(&engine, "transactions-wal-settings.csv"),
+            "id,available,held,total,locked,credit_limit\n\
+             1,-5.0000,0.0000,-5.0000,false,5.0000\n\
+             2,6.0000,4.0000,10.0000,false,0.0000\n\
+             3,3.0000,2.0000,5.0000,false,0.0000\n"
+        );
+        // client 1 loses its credit limit, disputes of withdrawals are not
+        // accepted anymore and new holds expire at once
+        let engine = Engine::new(*dealers)
+            .with_write_ahead_log(&wal)
+            .with_credit_limits(resource("accounts-credit-limit-changed.csv"))
+            .with_dispute_policy(DisputePolicy::DepositsOnly)
+            .with_hold_ttl(Duration::from_secs(0));
+        assert_eq!(
+            run(&engine, "transactions-wal-settings-changed.csv"),
+            "id,available,held,total,locked,credit_limit\n\
+             1,-5.0000,0.0000,-5.0000,false,0.0000\n\
+             2,6.0000,4.0000,10.0000,false,1.0000\n\
+             3,3.0000,2.0000,5.0000,false,0.0000\n",
+            "{} dealers",
+            dealers
+        );
+    }
+}
+
+#[test]
+fn account_locked_is_published_once_per_lock() {
+    for dealers in DEALERS {
+        let mut engine = Engine::new(*dealers);
+        let mut events = engine.subscribe();
+        assert_eq!(
+            run(&engine, "transactions-chargeback-locked.csv"),
+            "id,available,held,total,locked,credit_limit\n\
+             1,0.0000,0.0000,0.0000,true,0.0000\n"
+        );
+        let locked: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter(|e| matches!(e, AccountEvent::AccountLocked { .. }))
+            .collect();
+        assert_eq!(locked, [AccountEvent::AccountLocked { client: 1 }]);
+    }
+}
+
+/// Account storage that refuses to store the account of the given client
+/// once it has been stored the given number of times.
+struct RefusingAccountStorage {
+    accounts: InMemoryAccountStorage,
+    client: AccountId,
+    writes: usize,
+}
+
+impl RefusingAccountStorage {
+    fn storage(client: AccountId, writes: usize) -> Storage {
+        Storage::with_backends(
+            Box::new(Self {
+                accounts: InMemoryAccountStorage::new(),
+                client,
+                writes,
+            }),
+            Box::new(InMemoryTransactionStorage::new()),
+            Box::new(InMemoryDisputeRegister::new()),
+        )
+    }
+}
+
+impl AccountStorage for RefusingAccountStorage {
+    fn set(&mut self, account: Account) -> Result<(), Account> {
+        if account.id() == self.client {
+            if self.writes == 0 {
+                return Err(account);
+            }
+            self.writes -= 1;
+        }
+        self.accounts.set(account)
+    }
+
+    fn accounts(&self) -> Box<dyn Iterator<Item = &Account> + '_> {
+        self.accounts.accounts()
+    }
+}
+
+#[test]
+fn transfer_is_not_applied_if_an_account_can_not_be_stored() {
+    // the recipient, or the sender after it has been opened and credited
+    for (client, writes) in [(2, 0), (1, 2)] {
+        let report = assert_accounts(
+            "transactions-transfer-not-stored.csv",
+            |e| {
+                e.with_storage(move |_| {
+                    RefusingAccountStorage::storage(client, writes)
+                })
+            },
+            "id,available,held,total,locked,credit_limit\n\
+             1,10.0000,0.0000,10.0000,false,0.0000\n",
+        );
+        assert_eq!(
+            report,
+            "deposit,1,1,10.0000,,deposited,\n\
+             transfer,1,2,4.0000,2,rejected,account not stored"
+        );
+    }
+}
+
+#[test]
+fn ignored_duplicates_are_reported() {
+    let report = assert_accounts(
+        "transactions-duplicate.csv",
+        |e| e.with_duplicate_policy(DuplicatePolicy::Ignore),
+        "id,available,held,total,locked,credit_limit\n\
+         9,9.0000,0.0000,9.0000,false,0.0000\n",
+    );
+    assert_eq!(
+        report,
+        "deposit,10,1,10.0000,,ignored,duplicate transaction id\n\
+         deposit,9,1,10.0000,,deposited,\n\
+         deposit,9,1,10.0000,,ignored,duplicate transaction id\n\
+         withdrawal,9,2,1.0000,,ignored,duplicate transaction id\n\
+         withdrawal,9,2,1.0000,,withdrawn,"
+    );
+}
+
+#[test]
+fn references_of_other_clients_are_rejected_on_every_dealer() {
+    let report = assert_accounts(
+        "transactions-transfer-client-mismatch.csv",
+        |e| e,
+        "id,available,held,total,locked,credit_limit\n\
+         1,6.0000,0.0000,6.0000,false,0.0000\n\
+         2,4.0000,0.0000,4.0000,false,0.0000\n",
+    );
+    assert_eq!(
+        report,
+        "deposit,1,1,10.0000,,deposited,\n\
+         chargeback,6,2,,,rejected,client ids do not match\n\
+         dispute,1,2,,,disputed,\n\
+         dispute,2,2,,,rejected,client ids do not match\n\
+         dispute,3,2,,,rejected,client ids do not match\n\
+         resolve,1,2,,,resolved,\n\
+         resolve,2,2,,,rejected,client ids do not match\n\
+         transfer,1,2,4.0000,2,transferred,"
+    );
+}
+
+#[test]
+fn disputes_follow_the_dispute_states() {
+    let report = assert_accounts(
+        "transactions-dispute-lifecycle.csv",
+        |e| e,
+        "id,available,held,total,locked,credit_limit\n\
+         1,10.0000,0.0000,10.0000,false,0.0000\n",
+    );
+    assert_eq!(
+        report,
+        "chargeback,1,1,,,rejected,Chargeback not allowed on normal transaction\n\
+         deposit,1,1,10.0000,,deposited,\n\
+         dispute,1,1,,,disputed,\n\
+         dispute,1,1,,,rejected,Dispute not allowed on disputed transaction\n\
+         resolve,1,1,,,rejected,Resolve not allowed on normal transaction\n\
+         resolve,1,1,,,rejected,Resolve not allowed on resolved transaction\n\
+         resolve,1,1,,,resolved,\n\
+         dispute,1,9,,,rejected,referenced transaction not existing"
+    );
+}
+
+#[test]
+fn settled_disputes_are_not_reopened() {
+    let report = assert_accounts(
+        "transactions-dispute-twice.csv",
+        |e| e,
+        "id,available,held,total,locked,credit_limit\n\
+         9,10.0000,0.0000,10.0000,true,0.0000\n",
+    );
+    assert_eq!(
+        report,
+        "deposit,9,1,10.0000,,deposited,\n\
+         dispute,9,1,,,disputed,\n\
+         dispute,9,1,,,rejected,Dispute not allowed on resolved transaction\n\
+         resolve,9,1,,,resolved,\n\
+         chargeback,9,2,,,charged_back,\n\
+         chargeback,9,2,,,rejected,Chargeback not allowed on charged back transaction\n\
+         deposit,9,2,5.0000,,deposited,\n\
+         dispute,9,2,,,disputed,\n\
+         dispute,9,2,,,rejected,account is locked\n\
+         resolve,9,2,,,rejected,Resolve not allowed on charged back transaction"
+    );
+}
+
+#[test]
+fn disputed_withdrawals_are_reversed_by_a_chargeback() {
+    let report = assert_accounts(
+        "transactions-dispute-withdrawal.csv",
+        |e| e,
+        "id,available,held,total,locked,credit_limit\n\
+         9,10.0000,0.0000,10.0000,true,0.0000\n",
+    );
+    assert_eq!(
+        report,
+        "deposit,9,1,10.0000,,deposited,\n\
+         chargeback,9,2,,,charged_back,\n\
+         dispute,9,2,,,disputed,\n\
+         withdrawal,9,2,4.0000,,withdrawn,"
+    );
+}
+
+#[test]
+fn disputed_withdrawals_are_confirmed_by_a_resolve() {
+    assert_accounts(
+        "transactions-dispute-withdrawal-resolve.csv",
+        |e| e,
+        "id,available,held,total,locked,credit_limit\n\
+         9,6.0000,0.0000,6.0000,false,0.0000\n",
+    );
+}
+
+#[test]
+fn deposits_only_rejects_disputes_of_withdrawals() {
+    let report = assert_accounts(
+        "transactions-dispute-withdrawal.csv",
+        |e| e.with_dispute_policy(DisputePolicy::DepositsOnly),
+        "id,available,held,total,locked,credit_limit\n\
+         9,6.0000,0.0000,6.0000,false,0.0000\n",
+    );
+    assert_eq!(
+        report,
+        "deposit,9,1,10.0000,,deposited,\n\
+         chargeback,9,2,,,rejected,Chargeback not allowed on normal transaction\n\
+         dispute,9,2,,,rejected,transaction type can not be disputed\n\
+         withdrawal,9,2,4.0000,,withdrawn,"
+    );
+}
+
+#[test]
+fn disputed_withdrawals_are_recovered_after_a_policy_change() {
+    for dealers in DEALERS {
+        let wal = temp(&format!("wal-dispute-policy-{}", dealers));
+        let engine = Engine::new(*dealers).with_write_ahead_log(&wal);
+        assert_eq!(
+            run(&engine, "transactions-dispute-withdrawal-open.csv"),
+            "id,available,held,total,locked,credit_limit\n\
+             9,6.0000,4.0000,10.0000,false,0.0000\n"
+        );
+        // the open dispute is still settled
+        let engine = Engine::new(*dealers)
+            .with_write_ahead_log(&wal)
+            .with_dispute_policy(DisputePolicy::DepositsOnly);
+        assert_eq!(
+            run(&engine, "transactions-dispute-withdrawal-settle.csv"),
+            "id,available,held,total,locked,credit_limit\n\
+             9,10.0000,0.0000,10.0000,true,0.0000\n",
+            "{} dealers",
+            dealers
+        );
+    }
+}
+
+#[test]
+fn account_states_restrict_transactions() {
+    let report = assert_accounts(
+        "transactions-account-state.csv",
+        |e| e,
+        "id,available,held,total,locked,credit_limit\n\
+         1,12.0000,0.0000,12.0000,false,0.0000\n\
+         2,-2.0000,0.0000,-2.0000,false,0.0000\n",
+    );
+    assert_eq!(
+        report,
+        "deposit,1,1,10.0000,,deposited,\n\
+         chargeback,2,2,,,charged_back,\n\
+         deposit,2,2,10.0000,,deposited,\n\
+         dispute,2,2,,,disputed,\n\
+         freeze,1,3,,,frozen,fraud suspicion\n\
+         withdrawal,1,4,1.0000,,rejected,Withdrawal not accepted by frozen account\n\
+         transfer,2,5,2.0000,1,transferred,\n\
+         deposit,1,6,1.0000,,deposited,\n\
+         unfreeze,1,7,,,unfrozen,cleared by compliance\n\
+         withdrawal,1,8,1.0000,,withdrawn,\n\
+         unlock,2,9,,,unlocked,chargeback settled\n\
+         close,2,10,,,closed,requested by client\n\
+         deposit,2,11,1.0000,,rejected,Deposit not accepted by closed account"
+    );
+}
+
+#[test]
+fn authorizations_are_captured_voided_and_expired() {
+    let report = assert_accounts(
+        "transactions-authorization.csv",
+        |e| e,
+        "id,available,held,total,locked,credit_limit\n\
+         1,4.0000,0.0000,4.0000,false,0.0000\n",
+    );
+    assert_eq!(
+        report,
+        "deposit,1,1,10.0000,,deposited,\n\
+         authorize,1,2,6.0000,,authorized,\n\
+         capture,1,2,,,captured,\n\
+         capture,1,2,2.5000,,captured,\n\
+         dispute,1,2,,,rejected,transaction type can not be disputed\n\
+         withdrawal,1,3,5.0000,,rejected,insufficient funds\n\
+         authorize,1,4,3.0000,,authorized,\n\
+         void,1,4,,,voided,\n\
+         authorize,1,5,1.0000,,authorized,\n\
+         capture,1,5,,,rejected,Capture not allowed on expired authorization\n\
+         expire,1,5,,,expired,"
+    );
+}
+
+#[test]
+fn credit_limits_are_honored() {
+    let report = assert_accounts(
+        "transactions-credit-limit.csv",
+        |e| e.with_credit_limits(resource("accounts-credit-limit-transfer.csv")),
+        "id,available,held,total,locked,credit_limit\n\
+         1,-25.0000,0.0000,-25.0000,false,30.0000\n\
+         2,-5.0000,0.0000,-5.0000,false,5.0000\n",
+    );
+    assert_eq!(
+        report,
+        "deposit,1,1,10.0000,,deposited,\n\
+         withdrawal,1,2,40.0000,,withdrawn,\n\
+         authorize,1,3,25.0000,,rejected,insufficient funds\n\
+         transfer,2,4,5.0000,1,transferred,\n\
+         withdrawal,2,5,0.5000,,rejected,insufficient funds"
+    );
+}